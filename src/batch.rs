@@ -0,0 +1,249 @@
+//! Match one pattern against lots of choices in parallel, and keep only the best ones.
+//!
+//! The choices are split into chunks and scored by several threads. The matchers keep their
+//! buffers in thread locals, so every thread reuses its own buffers among the choices.
+//! Instead of sorting all the matched choices, each thread keeps the top-k results in a
+//! bounded heap, and the heaps are merged at the end.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::batch::BatchMatcher;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = SkimMatcherV2::default();
+//! let choices = ["axbycz", "abc", "xyz", "abxc"];
+//! let results = BatchMatcher::new(&matcher).top_k(&choices, "abc", 2);
+//!
+//! assert_eq!(results.len(), 2);
+//! assert_eq!(results[0].index, 1); // "abc"
+//! ```
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::thread;
+
+use crate::{FuzzyMatcher, IndexType, ScoreType};
+
+/// choices less than this won't be worth spawning threads
+const MIN_CHOICES_PER_THREAD: usize = 1024;
+
+/// A matched choice of a batch
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct BatchMatch {
+    /// index of the choice in the input
+    pub index: usize,
+    pub score: ScoreType,
+    /// matched indices of characters, empty unless requested
+    pub indices: Vec<IndexType>,
+}
+
+impl BatchMatch {
+    /// higher score first, and the earlier choice first on ties
+    fn rank(&self) -> (ScoreType, Reverse<usize>) {
+        (self.score, Reverse(self.index))
+    }
+}
+
+impl Ord for BatchMatch {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for BatchMatch {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub struct BatchMatcher<'a> {
+    matcher: &'a dyn FuzzyMatcher,
+    num_threads: usize,
+}
+
+impl<'a> BatchMatcher<'a> {
+    pub fn new(matcher: &'a dyn FuzzyMatcher) -> Self {
+        let num_threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Self {
+            matcher,
+            num_threads,
+        }
+    }
+
+    /// Set the maximum number of threads to use, defaults to the available parallelism
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = std::cmp::max(1, num_threads);
+        self
+    }
+
+    /// Return the best `k` matches sorted by score (the higher the better), ties are broken by
+    /// the index of the choice. The matched indices are not calculated.
+    pub fn top_k<S: AsRef<str> + Sync>(
+        &self,
+        choices: &[S],
+        pattern: &str,
+        k: usize,
+    ) -> Vec<BatchMatch> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let num_chunks = std::cmp::min(
+            self.num_threads,
+            std::cmp::max(1, choices.len() / MIN_CHOICES_PER_THREAD),
+        );
+
+        let heap = if num_chunks <= 1 {
+            self.top_k_of_chunk(choices, 0, pattern, k)
+        } else {
+            // rounded up, there are choices for more than one chunk
+            let chunk_size = (choices.len() - 1) / num_chunks + 1;
+            thread::scope(|scope| {
+                let handles: Vec<_> = choices
+                    .chunks(chunk_size)
+                    .enumerate()
+                    .map(|(chunk_idx, chunk)| {
+                        let offset = chunk_idx * chunk_size;
+                        scope.spawn(move || self.top_k_of_chunk(chunk, offset, pattern, k))
+                    })
+                    .collect();
+
+                let mut merged = BinaryHeap::with_capacity(k + 1);
+                for handle in handles {
+                    let heap = handle.join().expect("batch matching thread panicked");
+                    for Reverse(item) in heap {
+                        push_bounded(&mut merged, item, k);
+                    }
+                }
+                merged
+            })
+        };
+
+        let mut results: Vec<BatchMatch> = heap.into_iter().map(|Reverse(item)| item).collect();
+        results.sort_unstable_by(|a, b| b.cmp(a));
+        results
+    }
+
+    /// Same as `top_k`, but the matched indices are calculated for the returned matches.
+    pub fn top_k_indices<S: AsRef<str> + Sync>(
+        &self,
+        choices: &[S],
+        pattern: &str,
+        k: usize,
+    ) -> Vec<BatchMatch> {
+        let mut results = self.top_k(choices, pattern, k);
+        for result in results.iter_mut() {
+            if let Some((_, indices)) = self
+                .matcher
+                .fuzzy_indices(choices[result.index].as_ref(), pattern)
+            {
+                result.indices = indices;
+            }
+        }
+        results
+    }
+
+    fn top_k_of_chunk<S: AsRef<str>>(
+        &self,
+        chunk: &[S],
+        offset: usize,
+        pattern: &str,
+        k: usize,
+    ) -> BinaryHeap<Reverse<BatchMatch>> {
+        let mut heap = BinaryHeap::with_capacity(std::cmp::min(k, chunk.len()) + 1);
+        for (idx, choice) in chunk.iter().enumerate() {
+            if let Some(score) = self.matcher.fuzzy_match(choice.as_ref(), pattern) {
+                let item = BatchMatch {
+                    index: offset + idx,
+                    score,
+                    indices: Vec::new(),
+                };
+                push_bounded(&mut heap, item, k);
+            }
+        }
+        heap
+    }
+}
+
+/// push the item into the min-heap, keeping at most `k` best items
+fn push_bounded(heap: &mut BinaryHeap<Reverse<BatchMatch>>, item: BatchMatch, k: usize) {
+    if heap.len() < k {
+        heap.push(Reverse(item));
+    } else if let Some(mut worst) = heap.peek_mut() {
+        if item > worst.0 {
+            *worst = Reverse(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clangd::ClangdMatcher;
+    use crate::skim::SkimMatcherV2;
+    use crate::util::filter_and_sort;
+
+    fn choices() -> Vec<String> {
+        (0..20000)
+            .map(|num| format!("{}/{}", num, num * 7))
+            .collect()
+    }
+
+    #[test]
+    fn test_top_k() {
+        let matcher = SkimMatcherV2::default();
+        let choices = ["axbycz", "abc", "xyz", "abxc", "ab"];
+        let results = BatchMatcher::new(&matcher).top_k(&choices, "abc", 10);
+        let order: Vec<&str> = results.iter().map(|r| choices[r.index]).collect();
+        assert_eq!(order, filter_and_sort(&matcher, "abc", &choices));
+        assert!(results.iter().all(|r| r.indices.is_empty()));
+
+        assert_eq!(
+            BatchMatcher::new(&matcher).top_k(&choices, "abc", 1)[0].index,
+            1
+        );
+        assert!(BatchMatcher::new(&matcher)
+            .top_k(&choices, "abc", 0)
+            .is_empty());
+        assert!(BatchMatcher::new(&matcher)
+            .top_k(&choices, "zzz", 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_top_k_indices() {
+        let matcher = ClangdMatcher::default();
+        let choices = ["axbycz", "abc"];
+        let results = BatchMatcher::new(&matcher).top_k_indices(&choices, "abc", 2);
+        assert_eq!(results[0].index, 1);
+        assert_eq!(results[0].indices, vec![0, 1, 2]);
+        assert_eq!(results[1].indices, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn test_parallel_same_as_sequential() {
+        let matcher = SkimMatcherV2::default();
+        let choices = choices();
+        let sequential = BatchMatcher::new(&matcher)
+            .num_threads(1)
+            .top_k(&choices, "17", 100);
+        let parallel = BatchMatcher::new(&matcher)
+            .num_threads(8)
+            .top_k(&choices, "17", 100);
+        assert_eq!(sequential.len(), 100);
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn test_ties_broken_by_index() {
+        let matcher = SkimMatcherV2::default();
+        let choices = vec!["abc"; 5000];
+        let results = BatchMatcher::new(&matcher)
+            .num_threads(4)
+            .top_k(&choices, "abc", 3);
+        let indices: Vec<usize> = results.iter().map(|r| r.index).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+}
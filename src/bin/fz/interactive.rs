@@ -0,0 +1,514 @@
+//! The interactive mode of `fz`, picking the lines on the terminal
+//!
+//! The lines are read from stdin in the background and ranked live while the query is typed,
+//! the keys are read from and the list is drawn on the tty so that stdin and stdout are left
+//! for the input and the picked lines.
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+use termion::{clear, cursor, style};
+
+use fuzzy_matcher::highlight::{Ansi, Markup};
+use fuzzy_matcher::rank::Ranking;
+use fuzzy_matcher::render::Renderer;
+use fuzzy_matcher::{FuzzyMatcher, MatchResult};
+
+use crate::options::Options;
+use crate::{build_matcher, match_choices, read_choices, write_lines, Outcome};
+
+/// The prompt line and the info line are above the list
+const HEADER_HEIGHT: usize = 2;
+const PROMPT: &str = "> ";
+/// The markers of the current and the selected lines, and a space
+const MARKER_WIDTH: usize = 3;
+
+enum Event {
+    Choice(String),
+    InputDone,
+    Key(Key),
+    Error(io::Error),
+}
+
+/// What the user decided
+#[derive(Eq, PartialEq, Debug)]
+enum Action {
+    /// the indices of the picked choices
+    Accept(Vec<usize>),
+    Abort,
+}
+
+/// Pick the lines of stdin interactively and print the picked ones
+pub fn run(options: &Options) -> io::Result<Outcome> {
+    let tty = termion::get_tty()
+        .map_err(|err| io::Error::new(err.kind(), format!("cannot open the terminal: {}", err)))?;
+    let (sender, receiver) = mpsc::channel();
+    spawn_reader(options.read0, sender.clone());
+    spawn_keys(tty.try_clone()?, sender);
+
+    let mut picker = Picker::new(options);
+    let action = {
+        let mut screen = tty.try_clone()?.into_raw_mode()?.into_alternate_screen()?;
+        'events: loop {
+            let (width, height) = termion::terminal_size_fd(&tty)?;
+            picker.draw(&mut screen, width as usize, height as usize)?;
+
+            let mut event = match receiver.recv() {
+                Ok(event) => event,
+                // the tty and stdin are both closed
+                Err(_) => break Action::Abort,
+            };
+            // handle all the pending events before drawing again
+            loop {
+                match event {
+                    Event::Choice(choice) => picker.push_choice(choice),
+                    Event::InputDone => picker.input_done(),
+                    Event::Key(key) => {
+                        if let Some(action) = picker.handle_key(key) {
+                            break 'events action;
+                        }
+                    }
+                    Event::Error(err) => return Err(err),
+                }
+                match receiver.try_recv() {
+                    Ok(next) => event = next,
+                    Err(_) => break,
+                }
+            }
+            picker.update();
+        }
+        // the screen is restored here, before the picked lines are printed
+    };
+
+    match action {
+        Action::Accept(picked) if !picked.is_empty() => {
+            let lines = picked.iter().map(|&index| picker.choices[index].clone());
+            write_lines(lines, options.print0)?;
+            Ok(Outcome::Matched)
+        }
+        Action::Accept(_) => Ok(Outcome::NoMatch),
+        Action::Abort => Ok(Outcome::Aborted),
+    }
+}
+
+fn spawn_reader(read0: bool, sender: Sender<Event>) {
+    thread::spawn(move || {
+        let read = read_choices(read0, |choice| {
+            // the picker is gone if the send fails, the rest is dropped as well
+            let _ = sender.send(Event::Choice(choice));
+        });
+        let _ = sender.send(match read {
+            Ok(()) => Event::InputDone,
+            Err(err) => Event::Error(err),
+        });
+    });
+}
+
+fn spawn_keys<R: io::Read + Send + 'static>(tty: R, sender: Sender<Event>) {
+    thread::spawn(move || {
+        for key in tty.keys() {
+            let event = match key {
+                Ok(key) => Event::Key(key),
+                Err(err) => Event::Error(err),
+            };
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// The state of the interactive mode, apart from the terminal
+struct Picker {
+    matcher: Box<dyn FuzzyMatcher>,
+    ranking: Option<Ranking>,
+    threshold: Option<f64>,
+    renderer: Renderer,
+    choices: Vec<String>,
+    /// the number of choices matched against the query so far
+    matched: usize,
+    /// whether stdin is still being read
+    reading: bool,
+    query: Vec<char>,
+    /// the position of the cursor in the query
+    cursor: usize,
+    /// the matches in the order they are listed
+    matches: Vec<(usize, MatchResult)>,
+    /// the position of the current line in the matches
+    current: usize,
+    /// the position of the first line shown
+    scroll: usize,
+    /// the lines that fit on the screen, for paging
+    page: usize,
+    /// the indices of the selected choices
+    selected: BTreeSet<usize>,
+}
+
+impl Picker {
+    fn new(options: &Options) -> Self {
+        let matcher = build_matcher(options);
+        Self {
+            renderer: Renderer::default().graphemes(matcher.indexes_graphemes()),
+            matcher,
+            ranking: options.ranking.clone(),
+            threshold: options.threshold,
+            choices: Vec::new(),
+            matched: 0,
+            reading: true,
+            query: options.pattern.chars().collect(),
+            cursor: options.pattern.chars().count(),
+            matches: Vec::new(),
+            current: 0,
+            scroll: 0,
+            page: 1,
+            selected: BTreeSet::new(),
+        }
+    }
+
+    fn push_choice(&mut self, choice: String) {
+        self.choices.push(choice);
+    }
+
+    fn input_done(&mut self) {
+        self.reading = false;
+    }
+
+    /// Match the choices read since the last update
+    fn update(&mut self) {
+        if self.matched == self.choices.len() {
+            return;
+        }
+
+        let pattern: String = self.query.iter().collect();
+        let matches = match_choices(
+            self.matcher.as_ref(),
+            &pattern,
+            self.threshold,
+            &self.choices,
+            self.matched,
+        );
+        self.matched = self.choices.len();
+        self.matches.extend(matches);
+        // the input order is kept until anything is typed
+        if let Some(ranking) = self.ranking.as_ref().filter(|_| !pattern.is_empty()) {
+            ranking.sort(&self.choices, &mut self.matches);
+        }
+        self.current = self.current.min(self.matches.len().saturating_sub(1));
+    }
+
+    /// Match all the choices again against the changed query
+    fn rematch(&mut self) {
+        self.matched = 0;
+        self.matches.clear();
+        self.current = 0;
+        self.scroll = 0;
+        self.update();
+    }
+
+    /// Handle the key, returns the action if the user is done
+    fn handle_key(&mut self, key: Key) -> Option<Action> {
+        let query_len = self.query.len();
+        match key {
+            Key::Char('\n') => return Some(Action::Accept(self.picked())),
+            Key::Esc | Key::Ctrl('c') | Key::Ctrl('g') => return Some(Action::Abort),
+            Key::Char('\t') => {
+                self.toggle();
+                self.move_by(1);
+            }
+            Key::BackTab => {
+                self.toggle();
+                self.move_by(-1);
+            }
+            Key::Up | Key::Ctrl('p') | Key::Ctrl('k') => self.move_by(-1),
+            Key::Down | Key::Ctrl('n') | Key::Ctrl('j') => self.move_by(1),
+            Key::PageUp => self.move_by(-(self.page as isize)),
+            Key::PageDown => self.move_by(self.page as isize),
+            Key::Left | Key::Ctrl('b') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => self.cursor = (self.cursor + 1).min(query_len),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = query_len,
+            Key::Char(ch) if !ch.is_control() => {
+                self.query.insert(self.cursor, ch);
+                self.cursor += 1;
+                self.rematch();
+            }
+            Key::Backspace | Key::Ctrl('h') if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.query.remove(self.cursor);
+                self.rematch();
+            }
+            Key::Delete | Key::Ctrl('d') if self.cursor < query_len => {
+                self.query.remove(self.cursor);
+                self.rematch();
+            }
+            Key::Ctrl('u') if self.cursor > 0 => {
+                self.query.drain(..self.cursor);
+                self.cursor = 0;
+                self.rematch();
+            }
+            Key::Ctrl('w') if self.cursor > 0 => {
+                // the word before the cursor, with the spaces after it
+                let mut start = self.cursor;
+                while start > 0 && self.query[start - 1] == ' ' {
+                    start -= 1;
+                }
+                while start > 0 && self.query[start - 1] != ' ' {
+                    start -= 1;
+                }
+                self.query.drain(start..self.cursor);
+                self.cursor = start;
+                self.rematch();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// The selected choices in the input order, or the current one if none is selected
+    fn picked(&self) -> Vec<usize> {
+        if self.selected.is_empty() {
+            self.matches
+                .get(self.current)
+                .map(|(index, _)| *index)
+                .into_iter()
+                .collect()
+        } else {
+            self.selected.iter().copied().collect()
+        }
+    }
+
+    fn toggle(&mut self) {
+        if let Some((index, _)) = self.matches.get(self.current) {
+            if !self.selected.remove(index) {
+                self.selected.insert(*index);
+            }
+        }
+    }
+
+    fn move_by(&mut self, lines: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.current = self.current.saturating_add_signed(lines).min(last);
+    }
+
+    /// Draw the prompt, the info line and the visible part of the list
+    fn draw<W: Write>(&mut self, out: &mut W, width: usize, height: usize) -> io::Result<()> {
+        self.page = height.saturating_sub(HEADER_HEIGHT).max(1);
+        if self.current < self.scroll {
+            self.scroll = self.current;
+        } else if self.current >= self.scroll + self.page {
+            self.scroll = self.current + 1 - self.page;
+        }
+
+        // keep the cursor on the screen by scrolling a long query
+        let query_width = width.saturating_sub(PROMPT.len() + 1);
+        let mut query_start = 0;
+        while query_start < self.cursor && self.query_width(query_start..self.cursor) > query_width
+        {
+            query_start += 1;
+        }
+        let query: String = self.query[query_start..].iter().collect();
+        let spans = self.renderer.render(&query, &[], query_width);
+        write!(
+            out,
+            "{}{}{}{}",
+            cursor::Goto(1, 1),
+            clear::CurrentLine,
+            PROMPT,
+            spans
+                .iter()
+                .map(|span| span.text.as_str())
+                .collect::<String>()
+        )?;
+
+        let mut info = format!("  {}/{}", self.matches.len(), self.choices.len());
+        if !self.selected.is_empty() {
+            info.push_str(&format!(" ({} selected)", self.selected.len()));
+        }
+        if self.reading {
+            info.push_str(" ..");
+        }
+        let spans = self.renderer.render(&info, &[], width);
+        write!(
+            out,
+            "{}{}{}",
+            cursor::Goto(1, 2),
+            clear::CurrentLine,
+            spans
+                .iter()
+                .map(|span| span.text.as_str())
+                .collect::<String>()
+        )?;
+
+        let visible = self.matches.iter().enumerate().skip(self.scroll);
+        for (row, (pos, (index, result))) in visible.take(self.page).enumerate() {
+            let is_current = pos == self.current;
+            let current_marker = if is_current { '>' } else { ' ' };
+            let selected_marker = if self.selected.contains(index) {
+                '*'
+            } else {
+                ' '
+            };
+            let spans = self.renderer.render(
+                &self.choices[*index],
+                &result.indices,
+                width.saturating_sub(MARKER_WIDTH),
+            );
+            write!(
+                out,
+                "{}{}{}{}{} {}{}",
+                cursor::Goto(1, (row + HEADER_HEIGHT + 1) as u16),
+                clear::CurrentLine,
+                if is_current { style::Bold.as_ref() } else { "" },
+                current_marker,
+                selected_marker,
+                Ansi::default().markup_spans(&spans),
+                style::Reset
+            )?;
+        }
+        write!(out, "{}", clear::AfterCursor)?;
+
+        let column = PROMPT.len() + self.query_width(query_start..self.cursor) + 1;
+        write!(out, "{}", cursor::Goto(column as u16, 1))?;
+        out.flush()
+    }
+
+    fn query_width(&self, range: std::ops::Range<usize>) -> usize {
+        self.renderer
+            .width(&self.query[range].iter().collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker(choices: &[&str]) -> Picker {
+        let mut picker = Picker::new(&Options::default());
+        for choice in choices {
+            picker.push_choice(choice.to_string());
+        }
+        picker.input_done();
+        picker.update();
+        picker
+    }
+
+    fn type_query(picker: &mut Picker, query: &str) {
+        for ch in query.chars() {
+            assert_eq!(picker.handle_key(Key::Char(ch)), None);
+        }
+    }
+
+    fn listed(picker: &Picker) -> Vec<&str> {
+        picker
+            .matches
+            .iter()
+            .map(|(index, _)| picker.choices[*index].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut picker = picker(&["src/main.rs", "README.md", "src/lib.rs"]);
+        // the input order without a query
+        assert_eq!(listed(&picker), ["src/main.rs", "README.md", "src/lib.rs"]);
+
+        type_query(&mut picker, "s");
+        assert_eq!(listed(&picker).len(), 2);
+        type_query(&mut picker, "l");
+        assert_eq!(listed(&picker), ["src/lib.rs"]);
+
+        picker.handle_key(Key::Backspace);
+        assert_eq!(picker.query, ['s']);
+        picker.handle_key(Key::Ctrl('u'));
+        assert!(picker.query.is_empty());
+        assert_eq!(listed(&picker).len(), 3);
+
+        // the choices read later are matched as well
+        type_query(&mut picker, "md");
+        picker.push_choice("doc.md".to_string());
+        picker.update();
+        assert_eq!(listed(&picker).len(), 2);
+        assert!(listed(&picker).contains(&"doc.md"));
+    }
+
+    #[test]
+    fn test_edit_query() {
+        let mut picker = picker(&[]);
+        type_query(&mut picker, "foo bar");
+        picker.handle_key(Key::Left);
+        picker.handle_key(Key::Char('x'));
+        assert_eq!(picker.query.iter().collect::<String>(), "foo baxr");
+        picker.handle_key(Key::Home);
+        picker.handle_key(Key::Delete);
+        picker.handle_key(Key::End);
+        picker.handle_key(Key::Ctrl('w'));
+        assert_eq!(picker.query.iter().collect::<String>(), "oo ");
+        assert_eq!(picker.cursor, 3);
+    }
+
+    #[test]
+    fn test_pick() {
+        let mut picker = picker(&["a", "b", "c"]);
+        assert_eq!(picker.handle_key(Key::Down), None);
+        assert_eq!(
+            picker.handle_key(Key::Char('\n')),
+            Some(Action::Accept(vec![1]))
+        );
+
+        // select "c" and "a"
+        picker.handle_key(Key::Down);
+        picker.handle_key(Key::Char('\t'));
+        picker.handle_key(Key::Up);
+        picker.handle_key(Key::Up);
+        picker.handle_key(Key::Char('\t'));
+        assert_eq!(picker.current, 1);
+        assert_eq!(
+            picker.handle_key(Key::Char('\n')),
+            Some(Action::Accept(vec![0, 2]))
+        );
+
+        // unselect "a"
+        picker.handle_key(Key::Up);
+        picker.handle_key(Key::BackTab);
+        assert_eq!(
+            picker.handle_key(Key::Char('\n')),
+            Some(Action::Accept(vec![2]))
+        );
+
+        assert_eq!(picker.handle_key(Key::Esc), Some(Action::Abort));
+        assert_eq!(picker.handle_key(Key::Ctrl('c')), Some(Action::Abort));
+
+        type_query(&mut picker, "x");
+        assert_eq!(
+            picker.handle_key(Key::Char('\n')),
+            Some(Action::Accept(vec![2]))
+        );
+        picker.selected.clear();
+        assert_eq!(
+            picker.handle_key(Key::Char('\n')),
+            Some(Action::Accept(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut picker = picker(&["abc", "xbx", "b"]);
+        type_query(&mut picker, "b");
+        picker.handle_key(Key::Char('\t'));
+
+        let mut out = Vec::new();
+        picker.draw(&mut out, 20, 3).unwrap();
+        let screen = String::from_utf8(out).unwrap();
+        assert!(screen.contains("> b"));
+        assert!(screen.contains("  3/3 (1 selected)"));
+        // only one line of the list fits, the current one is kept visible
+        assert_eq!(picker.page, 1);
+        assert_eq!(picker.scroll, 1);
+    }
+}
@@ -0,0 +1,322 @@
+//! `fz`, a fuzzy filter for the command line, e.g. `ls | fz main`
+
+mod interactive;
+mod options;
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+
+use fuzzy_matcher::clangd::ClangdMatcher;
+use fuzzy_matcher::extended::ExtendedMatcher;
+use fuzzy_matcher::field::FieldMatcher;
+use fuzzy_matcher::fzf::FzfV1Matcher;
+use fuzzy_matcher::highlight::{Ansi, Markup, Segments};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::{normalize_score, FuzzyMatcher, MatchResult};
+
+use crate::options::{Algorithm, CaseMatching, Color, Options, USAGE};
+
+const EXIT_MATCHED: i32 = 0;
+const EXIT_NO_MATCH: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+const EXIT_ABORTED: i32 = 130;
+
+pub fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("fz: {}\nTry 'fz --help' for more information.", err);
+            exit(EXIT_ERROR);
+        }
+    };
+    if options.help {
+        print!("{}", USAGE);
+        exit(EXIT_MATCHED);
+    }
+
+    let outcome = if options.interactive {
+        interactive::run(&options)
+    } else {
+        run(&options)
+    };
+    match outcome {
+        Ok(Outcome::Matched) => exit(EXIT_MATCHED),
+        Ok(Outcome::NoMatch) => exit(EXIT_NO_MATCH),
+        Ok(Outcome::Aborted) => exit(EXIT_ABORTED),
+        Err(err) => {
+            eprintln!("fz: {}", err);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+pub enum Outcome {
+    /// any line is printed
+    Matched,
+    NoMatch,
+    /// the interactive mode is quit without accepting
+    Aborted,
+}
+
+/// Filter the input and print the matches
+fn run(options: &Options) -> io::Result<Outcome> {
+    let mut choices = Vec::new();
+    read_choices(options.read0, |choice| choices.push(choice))?;
+    let matcher = build_matcher(options);
+
+    let mut matches = match_choices(
+        matcher.as_ref(),
+        &options.pattern,
+        options.threshold,
+        &choices,
+        0,
+    );
+    if let Some(ranking) = options.ranking.as_ref() {
+        ranking.sort(&choices, &mut matches);
+    }
+    if let Some(limit) = options.limit {
+        matches.truncate(limit);
+    }
+
+    let color = match options.color {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => !options.json && termion::is_tty(&io::stdout()),
+    };
+    let lines = matches.iter().map(|(index, result)| {
+        let choice = choices[*index].as_str();
+        if options.json {
+            return json(*index, choice, result);
+        }
+
+        let text = if color {
+            Ansi::default().markup(Segments::from_ranges(choice, &result.ranges))
+        } else {
+            choice.to_string()
+        };
+        if options.scores {
+            format!("{}\t{}", result.score, text)
+        } else {
+            text
+        }
+    });
+    write_lines(lines, options.print0)?;
+
+    if matches.is_empty() {
+        Ok(Outcome::NoMatch)
+    } else {
+        Ok(Outcome::Matched)
+    }
+}
+
+/// Read the choices from stdin one by one, separated by new lines or NUL characters
+fn read_choices(read0: bool, mut on_choice: impl FnMut(String)) -> io::Result<()> {
+    let separator = if read0 { b'\0' } else { b'\n' };
+    let mut reader = io::stdin().lock();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(separator, &mut buf)? == 0 {
+            return Ok(());
+        }
+        if buf.last() == Some(&separator) {
+            buf.pop();
+        }
+        if !read0 && buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        on_choice(String::from_utf8_lossy(&buf).into_owned());
+    }
+}
+
+/// Print the lines to stdout, a closed stdout (e.g. piped to `head`) is not an error
+fn write_lines<I: IntoIterator<Item = String>>(lines: I, print0: bool) -> io::Result<()> {
+    let separator = if print0 { b'\0' } else { b'\n' };
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let written = lines.into_iter().try_for_each(|line| {
+        out.write_all(line.as_bytes())?;
+        out.write_all(&[separator])
+    });
+
+    match written.and_then(|_| out.flush()) {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Match the choices from `start` on, the indices are the ones among all the choices. The
+/// scores are normalized for the threshold by the best score of the pattern.
+fn match_choices(
+    matcher: &dyn FuzzyMatcher,
+    pattern: &str,
+    threshold: Option<f64>,
+    choices: &[String],
+    start: usize,
+) -> Vec<(usize, MatchResult)> {
+    let max_score = matcher.max_score(pattern);
+    choices
+        .iter()
+        .enumerate()
+        .skip(start)
+        .filter_map(|(index, choice)| {
+            matcher
+                .fuzzy_result(choice, pattern)
+                .map(|result| (index, result))
+        })
+        .filter(|(_, result)| match threshold {
+            Some(threshold) => normalize_score(result.score, max_score) >= threshold,
+            None => true,
+        })
+        .collect()
+}
+
+fn build_matcher(options: &Options) -> Box<dyn FuzzyMatcher> {
+    let case = options.case;
+    let matcher: Box<dyn FuzzyMatcher> = match options.algorithm {
+        Algorithm::Skim | Algorithm::Fzf => {
+            let matcher = if options.algorithm == Algorithm::Fzf {
+                SkimMatcherV2::default().fzf_compat()
+            } else {
+                SkimMatcherV2::default()
+            };
+            let matcher = matcher.graphemes(true);
+            Box::new(match case {
+                CaseMatching::Respect => matcher.respect_case(),
+                CaseMatching::Ignore => matcher.ignore_case(),
+                CaseMatching::Smart => matcher.smart_case(),
+            })
+        }
+        Algorithm::Clangd => {
+            let matcher = ClangdMatcher::default().graphemes(true);
+            Box::new(match case {
+                CaseMatching::Respect => matcher.respect_case(),
+                CaseMatching::Ignore => matcher.ignore_case(),
+                CaseMatching::Smart => matcher.smart_case(),
+            })
+        }
+        Algorithm::FzfV1 => {
+            let matcher = FzfV1Matcher::default();
+            Box::new(match case {
+                CaseMatching::Respect => matcher.respect_case(),
+                CaseMatching::Ignore => matcher.ignore_case(),
+                CaseMatching::Smart => matcher.smart_case(),
+            })
+        }
+    };
+
+    let matcher: Box<dyn FuzzyMatcher> = match options.nth.as_ref() {
+        Some(nth) => Box::new(
+            FieldMatcher::new(matcher)
+                .delimiter(&options.delimiter)
+                .nth(nth.clone()),
+        ),
+        None => matcher,
+    };
+
+    if options.extended {
+        let matcher = ExtendedMatcher::new(matcher);
+        Box::new(match case {
+            CaseMatching::Respect => matcher.respect_case(),
+            CaseMatching::Ignore => matcher.ignore_case(),
+            CaseMatching::Smart => matcher.smart_case(),
+        })
+    } else {
+        matcher
+    }
+}
+
+/// A JSON object of the match, e.g. `{"index":0,"score":56,"text":"abc","indices":[0,2]}`.
+/// The indices are the ones of the characters in the matched ranges, whether the matcher
+/// indexes the characters or the grapheme clusters.
+fn json(index: usize, choice: &str, result: &MatchResult) -> String {
+    let indices: Vec<String> = choice
+        .char_indices()
+        .enumerate()
+        .filter(|(_, (offset, _))| result.ranges.iter().any(|range| range.contains(offset)))
+        .map(|(idx, _)| idx.to_string())
+        .collect();
+    format!(
+        "{{\"index\":{},\"score\":{},\"text\":{},\"indices\":[{}]}}",
+        index,
+        result.score,
+        json_string(choice),
+        indices.join(",")
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let result = MatchResult::new("a\"b", 10, vec![0, 2]);
+        assert_eq!(
+            json(3, "a\"b", &result),
+            r#"{"index":3,"score":10,"text":"a\"b","indices":[0,2]}"#
+        );
+        assert_eq!(json_string("\\\t\u{1}é"), r#""\\\t\u0001é""#);
+
+        // the characters of the matched clusters
+        let result = MatchResult::from_graphemes("cafe\u{301} bar", 0, vec![3, 5]);
+        assert!(json(0, "cafe\u{301} bar", &result).ends_with(r#""indices":[3,4,6]}"#));
+    }
+
+    fn parse(args: &[&str]) -> Options {
+        Options::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_build_matcher() {
+        for algo in ["skim", "clangd", "fzf", "fzf-v1"] {
+            let options = parse(&["--algo", algo, "abc"]);
+            let matcher = build_matcher(&options);
+            assert!(matcher.fuzzy_result("xyz", "abc").is_none(), "{}", algo);
+            assert!(matcher.fuzzy_result("abx", "abc").is_none(), "{}", algo);
+            assert!(matcher.fuzzy_result("a_b_c", "abc").is_some(), "{}", algo);
+        }
+
+        let choices = vec!["cafe\u{301} bar".to_string(), "xyz".to_string()];
+        let options = parse(&["b"]);
+        let matches = match_choices(build_matcher(&options).as_ref(), "b", None, &choices, 0);
+        assert_eq!(matches.len(), 1);
+        assert!(json(0, &choices[0], &matches[0].1).ends_with(r#""indices":[6]}"#));
+
+        let options = parse(&["-x", "'b"]);
+        let matches = match_choices(build_matcher(&options).as_ref(), "'b", None, &choices, 0);
+        assert!(json(0, &choices[0], &matches[0].1).ends_with(r#""indices":[6]}"#));
+    }
+
+    #[test]
+    fn test_threshold() {
+        let choices: Vec<String> = ["abc", "a_b_c", "axxbxxc", "xxxxaxxxxxbxxxxxxc"]
+            .iter()
+            .map(|choice| choice.to_string())
+            .collect();
+        for algo in ["skim", "clangd", "fzf", "fzf-v1"] {
+            let matcher = build_matcher(&parse(&["--algo", algo, "abc"]));
+            let matches = match_choices(matcher.as_ref(), "abc", Some(0.5), &choices, 0);
+            let indices: Vec<usize> = matches.iter().map(|(index, _)| *index).collect();
+            assert_eq!(indices, [0, 1, 2], "{}", algo);
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Command line options of `fz`
+
+use fuzzy_matcher::field::FieldRange;
+use fuzzy_matcher::rank::Ranking;
+
+pub const USAGE: &str = "\
+Usage: fz [OPTIONS] <PATTERN>
+       fz --interactive [OPTIONS] [QUERY]
+
+Filter the lines of stdin by the fuzzy pattern, best matches first.
+
+The interactive mode picks the lines on the terminal: type to filter, Up and Down to move,
+Tab to select multiple lines, Enter to print the selected lines (or the current one) and
+Esc to quit.
+
+Options:
+  -a, --algo <ALGO>          skim (default), clangd, fzf or fzf-v1
+  -i, --ignore-case          Match case insensitively
+      --smart-case           Match case insensitively unless the pattern has upper
+                             case characters (default)
+      --respect-case         Match case sensitively
+  -x, --extended             Parse the pattern as an extended search query,
+                             e.g. \"^src .rs$ !test\"
+  -n, --nth <FIELDS>         Match only the selected fields, e.g. \"1,3..\"
+  -d, --delimiter <STR>      The delimiter of the fields (default: tab)
+      --no-sort              Keep the order of the input
+      --tiebreak <CRITERIA>  Break the ties of the scores by a comma separated
+                             list of length, begin, end and index
+      --limit <N>            Print at most N matches
+      --threshold <SCORE>    Drop the matches whose score relative to the best
+                             possible one, in [0, 1], is below SCORE
+      --read0                Read NUL separated input
+      --print0               Print NUL separated output
+  -z, --null                 Both --read0 and --print0
+      --json                 Print a JSON object with the score and the character
+                             indices of the matched text for every match
+  -s, --scores               Print the scores before the matches
+      --color <WHEN>         Highlight the matched characters: auto (default),
+                             always or never
+  -I, --interactive          Pick the lines interactively, starting with the query
+  -h, --help                 Print this help
+
+The exit status is 0 if any line is printed, 1 if none, 2 on errors and 130 if the
+interactive mode is quit.
+";
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Algorithm {
+    Skim,
+    Clangd,
+    Fzf,
+    FzfV1,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum CaseMatching {
+    Respect,
+    Ignore,
+    Smart,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub pattern: String,
+    pub algorithm: Algorithm,
+    pub case: CaseMatching,
+    pub extended: bool,
+    pub nth: Option<Vec<FieldRange>>,
+    pub delimiter: String,
+    /// None if the input order is kept
+    pub ranking: Option<Ranking>,
+    pub limit: Option<usize>,
+    pub threshold: Option<f64>,
+    pub read0: bool,
+    pub print0: bool,
+    pub json: bool,
+    pub scores: bool,
+    pub color: Color,
+    pub interactive: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            pattern: String::new(),
+            algorithm: Algorithm::Skim,
+            case: CaseMatching::Smart,
+            extended: false,
+            nth: None,
+            delimiter: "\t".to_string(),
+            ranking: Some(Ranking::default()),
+            limit: None,
+            threshold: None,
+            read0: false,
+            print0: false,
+            json: false,
+            scores: false,
+            color: Color::Auto,
+            interactive: false,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parse the arguments, without the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut pattern = None;
+        let mut tiebreak = None;
+        let mut sort = true;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // "--name=value" is the same as "--name value"
+            let (name, mut inline_value) = match arg.find('=') {
+                Some(pos) if arg.starts_with("--") => {
+                    (arg[..pos].to_string(), Some(arg[pos + 1..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .take()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("missing value of {}", name))
+            };
+
+            match name.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-a" | "--algo" => {
+                    options.algorithm = match value()?.as_str() {
+                        "skim" | "skim_v2" => Algorithm::Skim,
+                        "clangd" => Algorithm::Clangd,
+                        "fzf" => Algorithm::Fzf,
+                        "fzf-v1" => Algorithm::FzfV1,
+                        algo => return Err(format!("unknown algorithm: {}", algo)),
+                    }
+                }
+                "-i" | "--ignore-case" => options.case = CaseMatching::Ignore,
+                "--smart-case" => options.case = CaseMatching::Smart,
+                "--respect-case" => options.case = CaseMatching::Respect,
+                "-x" | "--extended" => options.extended = true,
+                "-n" | "--nth" => {
+                    let fields = value()?;
+                    let nth = FieldRange::parse_list(&fields);
+                    if nth.is_empty() {
+                        return Err(format!("invalid fields: {}", fields));
+                    }
+                    options.nth = Some(nth);
+                }
+                "-d" | "--delimiter" => options.delimiter = value()?,
+                "--sort" => sort = true,
+                "--no-sort" => sort = false,
+                "--tiebreak" => tiebreak = Some(value()?),
+                "--limit" => {
+                    let limit = value()?;
+                    options.limit = Some(
+                        limit
+                            .parse()
+                            .map_err(|_| format!("invalid limit: {}", limit))?,
+                    );
+                }
+                "--threshold" => {
+                    let threshold = value()?;
+                    options.threshold = Some(
+                        threshold
+                            .parse()
+                            .ok()
+                            .filter(|threshold| (0.0..=1.0).contains(threshold))
+                            .ok_or_else(|| format!("invalid threshold: {}", threshold))?,
+                    );
+                }
+                "--read0" => options.read0 = true,
+                "--print0" => options.print0 = true,
+                "-z" | "--null" => {
+                    options.read0 = true;
+                    options.print0 = true;
+                }
+                "--json" => options.json = true,
+                "-s" | "--scores" => options.scores = true,
+                "--color" => {
+                    options.color = match value()?.as_str() {
+                        "auto" => Color::Auto,
+                        "always" => Color::Always,
+                        "never" => Color::Never,
+                        when => return Err(format!("invalid color: {}", when)),
+                    }
+                }
+                "-I" | "--interactive" => options.interactive = true,
+                "--" => {
+                    if let Some(arg) = args.next() {
+                        if pattern.replace(arg).is_some() {
+                            return Err("too many patterns".to_string());
+                        }
+                    }
+                    break;
+                }
+                _ if name.starts_with('-') && name.len() > 1 => {
+                    return Err(format!("unknown option: {}", name));
+                }
+                _ if pattern.is_some() => return Err("too many patterns".to_string()),
+                _ => pattern = Some(arg),
+            }
+
+            if inline_value.is_some() {
+                return Err(format!("{} takes no value", name));
+            }
+        }
+
+        if args.next().is_some() {
+            return Err("too many patterns".to_string());
+        }
+        if options.help {
+            return Ok(options);
+        }
+        // the pattern is the initial query of the interactive mode
+        options.pattern = match pattern {
+            Some(pattern) => pattern,
+            None if options.interactive => String::new(),
+            None => return Err("missing pattern".to_string()),
+        };
+
+        options.ranking = if !sort {
+            None
+        } else if let Some(tiebreak) = tiebreak {
+            Some(
+                Ranking::parse(&format!("score,{}", tiebreak))
+                    .ok_or_else(|| format!("invalid tiebreak: {}", tiebreak))?,
+            )
+        } else if options.algorithm == Algorithm::Fzf || options.algorithm == Algorithm::FzfV1 {
+            Some(Ranking::fzf())
+        } else {
+            Some(Ranking::default())
+        };
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuzzy_matcher::rank::Criterion;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = parse(&["abc"]).unwrap();
+        assert_eq!(options.pattern, "abc");
+        assert_eq!(options.algorithm, Algorithm::Skim);
+        assert_eq!(options.case, CaseMatching::Smart);
+        assert_eq!(options.ranking, Some(Ranking::default()));
+
+        let options = parse(&[
+            "-i",
+            "--algo=clangd",
+            "--nth",
+            "2..",
+            "-d",
+            ":",
+            "--limit",
+            "3",
+            "--threshold=0.5",
+            "-z",
+            "--tiebreak",
+            "length,-index",
+            "--",
+            "-abc",
+        ])
+        .unwrap();
+        assert_eq!(options.pattern, "-abc");
+        assert_eq!(options.algorithm, Algorithm::Clangd);
+        assert_eq!(options.case, CaseMatching::Ignore);
+        assert_eq!(options.nth, Some(vec![FieldRange::Range(Some(2), None)]));
+        assert_eq!(options.delimiter, ":");
+        assert_eq!(options.limit, Some(3));
+        assert_eq!(options.threshold, Some(0.5));
+        assert!(options.read0 && options.print0);
+        assert_eq!(
+            options.ranking,
+            Some(
+                Ranking::new()
+                    .then(Criterion::Score)
+                    .then(Criterion::Length)
+                    .then_reversed(Criterion::Index)
+            )
+        );
+
+        assert_eq!(
+            parse(&["--algo", "fzf", "a"]).unwrap().ranking,
+            Some(Ranking::fzf())
+        );
+        assert_eq!(parse(&["--no-sort", "a"]).unwrap().ranking, None);
+        assert_eq!(parse(&[""]).unwrap().pattern, "");
+        assert!(parse(&["-h"]).unwrap().help);
+
+        let options = parse(&["-I"]).unwrap();
+        assert!(options.interactive);
+        assert_eq!(options.pattern, "");
+        assert_eq!(parse(&["--interactive", "abc"]).unwrap().pattern, "abc");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(&[]).unwrap_err(), "missing pattern");
+        assert_eq!(parse(&["a", "b"]).unwrap_err(), "too many patterns");
+        assert_eq!(parse(&["--limit"]).unwrap_err(), "missing value of --limit");
+        assert_eq!(
+            parse(&["--limit", "x", "a"]).unwrap_err(),
+            "invalid limit: x"
+        );
+        assert_eq!(
+            parse(&["--threshold", "2", "a"]).unwrap_err(),
+            "invalid threshold: 2"
+        );
+        assert_eq!(
+            parse(&["--algo", "x", "a"]).unwrap_err(),
+            "unknown algorithm: x"
+        );
+        assert_eq!(parse(&["--foo", "a"]).unwrap_err(), "unknown option: --foo");
+        assert_eq!(
+            parse(&["--json=1", "a"]).unwrap_err(),
+            "--json takes no value"
+        );
+        assert_eq!(
+            parse(&["--tiebreak", "foo", "a"]).unwrap_err(),
+            "invalid tiebreak: foo"
+        );
+        assert_eq!(
+            parse(&["--nth", "x", "a"]).unwrap_err(),
+            "invalid fields: x"
+        );
+    }
+}
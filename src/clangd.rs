@@ -1,30 +1,33 @@
-///! The fuzzy matching algorithm used in clangd.
-///! https://github.com/llvm-mirror/clang-tools-extra/blob/master/clangd/FuzzyMatch.cpp
-///!
-///! # Example:
-///! ```edition2018
-///! use fuzzy_matcher::FuzzyMatcher;
-///! use fuzzy_matcher::clangd::ClangdMatcher;
-///!
-///! let matcher = ClangdMatcher::default();
-///!
-///! assert_eq!(None, matcher.fuzzy_match("abc", "abx"));
-///! assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
-///! assert!(matcher.fuzzy_match("axbycz", "xyz").is_some());
-///!
-///! let (score, indices) = matcher.fuzzy_indices("axbycz", "abc").unwrap();
-///! assert_eq!(indices, [0, 2, 4]);
-///!
-///! ```
-///!
-///! Algorithm modified from
-///! https://github.com/llvm-mirror/clang-tools-extra/blob/master/clangd/FuzzyMatch.cpp
-///! Also check: https://github.com/lewang/flx/issues/98
+//! The fuzzy matching algorithm used in clangd.
+//! https://github.com/llvm-mirror/clang-tools-extra/blob/master/clangd/FuzzyMatch.cpp
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::clangd::ClangdMatcher;
+//!
+//! let matcher = ClangdMatcher::default();
+//!
+//! assert_eq!(None, matcher.fuzzy_match("abc", "abx"));
+//! assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
+//! assert!(matcher.fuzzy_match("axbycz", "xyz").is_some());
+//!
+//! let (score, indices) = matcher.fuzzy_indices("axbycz", "abc").unwrap();
+//! assert_eq!(indices, [0, 2, 4]);
+//!
+//! ```
+//!
+//! Algorithm modified from
+//! https://github.com/llvm-mirror/clang-tools-extra/blob/master/clangd/FuzzyMatch.cpp
+//! Also check: https://github.com/lewang/flx/issues/98
+
+use crate::classifier::{CharClassifier, CharType, ClangdClassifier};
+use crate::explain::{Contribution, Explanation, MatchedChar, ScoreKind};
 use crate::util::*;
-use crate::{FuzzyMatcher, IndexType, ScoreType};
+use crate::{CaseFolding, FuzzyMatcher, IndexType, ScoreType};
 use std::cell::RefCell;
 use std::cmp::max;
-use thread_local::CachedThreadLocal;
+use thread_local::ThreadLocal;
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 enum CaseMatching {
@@ -33,28 +36,102 @@ enum CaseMatching {
     Smart,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct ClangdScoreConfig {
+    /// Base score of every matched character
+    pub score_match: i32,
+
+    /// Bonus if the pattern so far is a (case-insensitive) prefix of the choice
+    pub bonus_prefix: i32,
+
+    /// Bonus if the matched characters are of the same case
+    pub bonus_case_match: i32,
+
+    /// Bonus for matching the head of a word segment, e.g. `b` in `foo_bar` or `B` in `fooBar`
+    pub bonus_head: i32,
+
+    /// Extra bonus if a head in the pattern aligns with a head in the choice
+    pub bonus_head_aligned: i32,
+
+    /// Penalty for matching inside a word segment while the previous character wasn't matched
+    pub penalty_tail_after_skip: i32,
+
+    /// Penalty for a head in the pattern matching in the middle of a word segment
+    pub penalty_head_in_tail: i32,
+
+    /// Penalty for matching the first pattern character in the middle of a word segment
+    pub penalty_first_char_in_tail: i32,
+
+    /// Penalty for every skipped character of the choice
+    pub gap_skip: i32,
+
+    /// Extra penalty for skipping a character right after a match, i.e. non-consecutive match
+    pub gap_after_match: i32,
+
+    /// Extra penalty for skipping a separator
+    pub gap_separator: i32,
+
+    /// The final score is adjusted by `penalty_length * floor(ln(num_chars + 1))`, so that
+    /// shorter choices are preferred
+    pub penalty_length: i32,
+}
+
+impl Default for ClangdScoreConfig {
+    fn default() -> Self {
+        Self {
+            score_match: 10,
+            bonus_prefix: 10,
+            bonus_case_match: 8,
+            bonus_head: 9,
+            bonus_head_aligned: 10,
+            penalty_tail_after_skip: -30,
+            penalty_head_in_tail: -10,
+            penalty_first_char_in_tail: -40,
+            gap_skip: -1,
+            gap_after_match: -3,
+            gap_separator: -6,
+            penalty_length: -1,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ClangdMatcher {
+    score_config: ClangdScoreConfig,
     case: CaseMatching,
+    case_folding: CaseFolding,
+    normalize: bool,
+    graphemes: bool,
+    classifier: Box<dyn CharClassifier>,
 
     use_cache: bool,
 
-    c_cache: CachedThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of choice
-    p_cache: CachedThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of pattern
+    c_cache: ThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of choice
+    p_cache: ThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of pattern
 }
 
 impl Default for ClangdMatcher {
     fn default() -> Self {
         Self {
+            score_config: ClangdScoreConfig::default(),
             case: CaseMatching::Ignore,
+            case_folding: CaseFolding::Ascii,
+            normalize: false,
+            graphemes: false,
+            classifier: Box::new(ClangdClassifier),
             use_cache: true,
-            c_cache: CachedThreadLocal::new(),
-            p_cache: CachedThreadLocal::new(),
+            c_cache: ThreadLocal::new(),
+            p_cache: ThreadLocal::new(),
         }
     }
 }
 
 impl ClangdMatcher {
+    pub fn score_config(mut self, score_config: ClangdScoreConfig) -> Self {
+        self.score_config = score_config;
+        self
+    }
+
     pub fn ignore_case(mut self) -> Self {
         self.case = CaseMatching::Ignore;
         self
@@ -70,104 +147,276 @@ impl ClangdMatcher {
         self
     }
 
+    /// Set how characters are folded in case insensitive matching, it also decides which
+    /// characters count as upper case for smart case.
+    pub fn case_folding(mut self, case_folding: CaseFolding) -> Self {
+        self.case_folding = case_folding;
+        self
+    }
+
+    /// Match the characters regardless of their diacritics, e.g. "resume" matches "résumé".
+    /// The indices still refer to the characters of the original choice.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Match the extended grapheme clusters instead of the characters, so that a visible glyph,
+    /// e.g. an emoji with modifiers or a letter with combining marks, is never split. A cluster
+    /// is matched by its first character, and the indices refer to the clusters, use
+    /// `MatchResult::from_graphemes` (or `fuzzy_result`) for their byte ranges.
+    ///
+    /// It only applies to the `&str` methods, the characters given to `fuzzy_indices_chars`
+    /// are taken as the units already. The wrappers of this crate, e.g. `PathMatcher`, index
+    /// the clusters as well.
+    pub fn graphemes(mut self, graphemes: bool) -> Self {
+        self.graphemes = graphemes;
+        self
+    }
+
+    /// Decide the word boundaries by a custom classification of characters, defaults to
+    /// `ClangdClassifier`.
+    pub fn char_classifier<C: CharClassifier + 'static>(mut self, classifier: C) -> Self {
+        self.classifier = Box::new(classifier);
+        self
+    }
+
     pub fn use_cache(mut self, use_cache: bool) -> Self {
         self.use_cache = use_cache;
         self
     }
 
-    fn contains_upper(&self, string: &str) -> bool {
-        for ch in string.chars() {
-            if ch.is_ascii_uppercase() {
-                return true;
-            }
+    fn is_case_sensitive(&self, pattern: &str) -> bool {
+        match self.case {
+            CaseMatching::Respect => true,
+            CaseMatching::Ignore => false,
+            CaseMatching::Smart => contains_upper(pattern, self.case_folding),
         }
-
-        false
     }
 
-    fn is_case_sensitive(&self, pattern: &str) -> bool {
+    fn is_case_sensitive_atoms<C: Copy + Into<char>>(&self, pattern: &[C]) -> bool {
         match self.case {
             CaseMatching::Respect => true,
             CaseMatching::Ignore => false,
-            CaseMatching::Smart => self.contains_upper(pattern),
+            CaseMatching::Smart => contains_upper_atoms(pattern, self.case_folding),
         }
     }
-}
 
-impl FuzzyMatcher for ClangdMatcher {
-    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+    /// Explain how the score of matching `choice` with `pattern` is calculated
+    #[allow(clippy::unnecessary_cast)]
+    pub fn explain(&self, choice: &str, pattern: &str) -> Option<Explanation> {
         let case_sensitive = self.is_case_sensitive(pattern);
 
-        let mut choice_chars = self
-            .c_cache
-            .get_or(|| RefCell::new(Vec::new()))
-            .borrow_mut();
-        let mut pattern_chars = self
-            .p_cache
-            .get_or(|| RefCell::new(Vec::new()))
-            .borrow_mut();
+        let mut choice_chars = Vec::new();
+        let mut pattern_chars = Vec::new();
+        extend_units(&mut choice_chars, choice, self.graphemes);
+        extend_units(&mut pattern_chars, pattern, self.graphemes);
+        let expand_folding = !case_sensitive && self.case_folding == CaseFolding::Full;
+        transform_chars(&mut pattern_chars, self.normalize, expand_folding);
+        let original_indices = transform_chars(&mut choice_chars, self.normalize, expand_folding);
+
+        cheap_matches(
+            &choice_chars,
+            &pattern_chars,
+            case_sensitive,
+            self.case_folding,
+        )?;
 
-        choice_chars.clear();
-        for char in choice.chars() {
-            choice_chars.push(char);
+        let num_pattern_chars = pattern_chars.len();
+        let num_choice_chars = choice_chars.len();
+
+        let dp = build_graph(
+            &choice_chars,
+            &pattern_chars,
+            false,
+            case_sensitive,
+            self.case_folding,
+            &self.score_config,
+            self.classifier.as_ref(),
+        );
+        let (score, mut indices) = best_path(&dp, num_pattern_chars, num_choice_chars);
+
+        let line_classes = classify(&choice_chars, self.classifier.as_ref());
+        let pattern_classes = classify(&pattern_chars, self.classifier.as_ref());
+
+        // replay the path, the penalties of skipped characters are given to the next match
+        let mut matches = Vec::with_capacity(num_pattern_chars);
+        let mut contributions = vec![];
+        let mut last_action = Action::Miss;
+        for (line_idx, &line_ch) in choice_chars.iter().enumerate() {
+            let pat_idx = matches.len();
+            if pat_idx > 0 && pat_idx == num_pattern_chars {
+                // skipping the characters after the last match is free
+                break;
+            }
+
+            let add = |kind, score: i32| {
+                contributions.push(Contribution {
+                    kind,
+                    score: ScoreType::from(score),
+                })
+            };
+
+            if pat_idx < num_pattern_chars && indices[pat_idx] as usize == line_idx {
+                match_contributions(
+                    pat_idx,
+                    pattern_chars[pat_idx],
+                    pattern_classes[pat_idx].1,
+                    line_idx,
+                    line_ch,
+                    line_classes[line_idx].1,
+                    Action::Match,
+                    &self.score_config,
+                    add,
+                );
+
+                let index = match original_indices.as_ref() {
+                    Some(original_indices) => original_indices[line_idx],
+                    None => line_idx,
+                };
+                matches.push(MatchedChar {
+                    index: index as IndexType,
+                    ch: line_ch,
+                    contributions: std::mem::take(&mut contributions),
+                });
+                last_action = Action::Match;
+            } else {
+                let (line_type, _) = line_classes[line_idx];
+                skip_contributions(line_idx, line_type, last_action, &self.score_config, add);
+                last_action = Action::Miss;
+            }
         }
 
-        pattern_chars.clear();
-        for char in pattern.chars() {
-            pattern_chars.push(char);
+        if let Some(original_indices) = original_indices.as_ref() {
+            map_original_indices(&mut indices, original_indices);
         }
 
-        if cheap_matches(&choice_chars, &pattern_chars, case_sensitive).is_none() {
-            return None;
+        // with an empty pattern, the skipped characters are not followed by any match
+        let mut adjustments = contributions;
+        let adjusted = adjust_score(score, num_choice_chars, &self.score_config);
+        if adjusted != score {
+            adjustments.push(Contribution {
+                kind: ScoreKind::Length,
+                score: adjusted - score,
+            });
         }
 
-        let num_pattern_chars = pattern_chars.len();
-        let num_choice_chars = choice_chars.len();
+        Some(Explanation {
+            score: adjusted,
+            indices,
+            matches,
+            adjustments,
+        })
+    }
+}
 
-        let dp = build_graph(&choice_chars, &pattern_chars, false, case_sensitive);
+impl ClangdMatcher {
+    fn fuzzy(
+        &self,
+        choice: &str,
+        pattern: &str,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if is_ascii_units(choice, self.graphemes) && is_ascii_units(pattern, self.graphemes) {
+            // ASCII characters are neither normalized nor expanded, match on the bytes directly
+            return self.fuzzy_bytes(choice.as_bytes(), pattern.as_bytes(), with_pos);
+        }
 
-        // search backwards for the matched indices
-        let mut indices_reverse = Vec::with_capacity(num_pattern_chars);
-        let cell = dp[num_pattern_chars][num_choice_chars];
+        let case_sensitive = self.is_case_sensitive(pattern);
 
-        let (mut last_action, score) = if cell.match_score > cell.miss_score {
-            (Action::Match, cell.match_score)
+        // the clusters are matched by their first characters, which may_match doesn't know
+        let result = if !self.graphemes
+            && !may_match(
+                choice,
+                pattern,
+                case_sensitive,
+                self.case_folding,
+                self.normalize,
+            ) {
+            None
         } else {
-            (Action::Miss, cell.miss_score)
+            let mut choice_chars = self
+                .c_cache
+                .get_or(|| RefCell::new(Vec::new()))
+                .borrow_mut();
+            let mut pattern_chars = self
+                .p_cache
+                .get_or(|| RefCell::new(Vec::new()))
+                .borrow_mut();
+
+            choice_chars.clear();
+            extend_units(&mut choice_chars, choice, self.graphemes);
+            pattern_chars.clear();
+            extend_units(&mut pattern_chars, pattern, self.graphemes);
+
+            self.fuzzy_transformed(
+                &mut choice_chars,
+                &mut pattern_chars,
+                case_sensitive,
+                with_pos,
+            )
         };
 
-        let mut row = num_pattern_chars;
-        let mut col = num_choice_chars;
+        self.release_caches();
+        result
+    }
 
-        while row > 0 || col > 0 {
-            if last_action == Action::Match {
-                indices_reverse.push((col - 1) as IndexType);
-            }
+    /// Same as `fuzzy`, but on the characters decoded already
+    fn fuzzy_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let case_sensitive = self.is_case_sensitive_atoms(pattern);
+        let result = if self.transforms(case_sensitive) {
+            self.fuzzy_copied(choice, pattern, case_sensitive, with_pos)
+        } else {
+            cheap_matches(choice, pattern, case_sensitive, self.case_folding)
+                .and_then(|_| self.fuzzy_atoms(choice, pattern, case_sensitive, with_pos))
+        };
 
-            let cell = &dp[row][col];
-            if last_action == Action::Match {
-                last_action = cell.last_action_match;
-                row -= 1;
-                col -= 1;
-            } else {
-                last_action = cell.last_action_miss;
-                col -= 1;
-            }
-        }
+        self.release_caches();
+        result
+    }
 
-        if !self.use_cache {
-            // drop the allocated memory
-            self.c_cache.get().map(|cell| cell.replace(vec![]));
-            self.p_cache.get().map(|cell| cell.replace(vec![]));
-        }
+    /// Same as `fuzzy`, but every byte is a character
+    fn fuzzy_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let case_sensitive = self.is_case_sensitive_atoms(pattern);
+        let result = if choice.is_ascii() && pattern.is_ascii() {
+            cheap_matches_ascii(choice, pattern, case_sensitive)
+                .and_then(|_| self.fuzzy_atoms(choice, pattern, case_sensitive, with_pos))
+        } else if self.transforms(case_sensitive) {
+            let choice: Vec<char> = choice.iter().map(|&b| b.into()).collect();
+            let pattern: Vec<char> = pattern.iter().map(|&b| b.into()).collect();
+            self.fuzzy_copied(&choice, &pattern, case_sensitive, with_pos)
+        } else {
+            cheap_matches(choice, pattern, case_sensitive, self.case_folding)
+                .and_then(|_| self.fuzzy_atoms(choice, pattern, case_sensitive, with_pos))
+        };
 
-        indices_reverse.reverse();
-        Some((adjust_score(score, num_choice_chars), indices_reverse))
+        self.release_caches();
+        result
     }
 
-    fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<ScoreType> {
-        let case_sensitive = self.is_case_sensitive(pattern);
+    /// Whether the characters would be transformed before matching, see `fuzzy_transformed`
+    fn transforms(&self, case_sensitive: bool) -> bool {
+        self.normalize || (!case_sensitive && self.case_folding == CaseFolding::Full)
+    }
 
+    /// Copy the characters into the caches to transform them, and match
+    fn fuzzy_copied(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
         let mut choice_chars = self
             .c_cache
             .get_or(|| RefCell::new(Vec::new()))
@@ -178,34 +427,172 @@ impl FuzzyMatcher for ClangdMatcher {
             .borrow_mut();
 
         choice_chars.clear();
-        for char in choice.chars() {
-            choice_chars.push(char);
+        choice_chars.extend_from_slice(choice);
+        pattern_chars.clear();
+        pattern_chars.extend_from_slice(pattern);
+
+        self.fuzzy_transformed(
+            &mut choice_chars,
+            &mut pattern_chars,
+            case_sensitive,
+            with_pos,
+        )
+    }
+
+    /// Transform the characters in place and match them, the indices are mapped back to the
+    /// ones of the original choice.
+    fn fuzzy_transformed(
+        &self,
+        choice_chars: &mut Vec<char>,
+        pattern_chars: &mut Vec<char>,
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        // strip the diacritics if needed, and with full case folding, e.g. "ß" should match
+        // "ss", so expand these characters and match them one by one.
+        let expand_folding = !case_sensitive && self.case_folding == CaseFolding::Full;
+        transform_chars(pattern_chars, self.normalize, expand_folding);
+        let original_indices = transform_chars(choice_chars, self.normalize, expand_folding);
+
+        let mut result = cheap_matches(
+            choice_chars,
+            pattern_chars,
+            case_sensitive,
+            self.case_folding,
+        )
+        .and_then(|_| self.fuzzy_atoms(choice_chars, pattern_chars, case_sensitive, with_pos));
+        if let (Some((_, indices)), Some(original_indices)) =
+            (result.as_mut(), original_indices.as_ref())
+        {
+            map_original_indices(indices, original_indices);
         }
+        result
+    }
 
-        pattern_chars.clear();
-        for char in pattern.chars() {
-            pattern_chars.push(char);
+    fn release_caches(&self) {
+        if !self.use_cache {
+            // drop the allocated memory
+            self.c_cache.get().map(|cell| cell.replace(vec![]));
+            self.p_cache.get().map(|cell| cell.replace(vec![]));
         }
+    }
+
+    /// Match the (transformed) characters, or the bytes if they are all ASCII, which have passed
+    /// the prefilter. The returned indices are the ones of `choice`.
+    fn fuzzy_atoms<C: Copy + Into<char>>(
+        &self,
+        choice: &[C],
+        pattern: &[C],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let num_pattern_chars = pattern.len();
+        let num_choice_chars = choice.len();
+
+        let dp = build_graph(
+            choice,
+            pattern,
+            !with_pos,
+            case_sensitive,
+            self.case_folding,
+            &self.score_config,
+            self.classifier.as_ref(),
+        );
 
-        if cheap_matches(&choice_chars, &pattern_chars, case_sensitive).is_none() {
+        let (score, indices) = if with_pos {
+            best_path(&dp, num_pattern_chars, num_choice_chars)
+        } else {
+            let cell = dp[num_pattern_chars & 1][num_choice_chars];
+            (max(cell.match_score, cell.miss_score), Vec::new())
+        };
+
+        Some((
+            adjust_score(score, num_choice_chars, &self.score_config),
+            indices,
+        ))
+    }
+}
+
+impl FuzzyMatcher for ClangdMatcher {
+    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy(choice, pattern, true)
+    }
+
+    fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<ScoreType> {
+        self.fuzzy(choice, pattern, false).map(|(score, _)| score)
+    }
+
+    /// Every unit of the pattern scores at most `score_match` plus the positive bonuses and
+    /// penalties, and the choice is at least as long as the pattern. None if skipping or length
+    /// is rewarded, as the score then grows with the choice.
+    ///
+    /// Note that a poor match may score below 0, so `fuzzy_match_normalized` clamps it to 0.0.
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        let config = &self.score_config;
+        if config.gap_skip > 0
+            || config.gap_after_match > 0
+            || config.gap_separator > 0
+            || config.penalty_length > 0
+        {
             return None;
         }
 
-        let num_pattern_chars = pattern_chars.len();
-        let num_choice_chars = choice_chars.len();
+        let max_per_char = config.score_match
+            + [
+                config.bonus_prefix,
+                config.bonus_case_match,
+                config.bonus_head,
+                config.bonus_head_aligned,
+                config.penalty_tail_after_skip,
+                config.penalty_head_in_tail,
+                config.penalty_first_char_in_tail,
+            ]
+            .iter()
+            .map(|&score| max(0, score))
+            .sum::<i32>();
+
+        let expand_folding =
+            !self.is_case_sensitive(pattern) && self.case_folding == CaseFolding::Full;
+        let num_pattern = count_units(pattern, self.graphemes, self.normalize, expand_folding);
+        if num_pattern == 0 {
+            return Some(0);
+        }
+        let max_score = ScoreType::from(max_per_char) * num_pattern as ScoreType;
+        Some(adjust_score(max_score, num_pattern, config))
+    }
 
-        let dp = build_graph(&choice_chars, &pattern_chars, true, case_sensitive);
+    fn narrows(&self) -> bool {
+        true
+    }
 
-        let cell = dp[num_pattern_chars & 1][num_choice_chars];
-        let score = max(cell.match_score, cell.miss_score);
+    fn indexes_graphemes(&self) -> bool {
+        self.graphemes
+    }
 
-        if !self.use_cache {
-            // drop the allocated memory
-            self.c_cache.get().map(|cell| cell.replace(vec![]));
-            self.p_cache.get().map(|cell| cell.replace(vec![]));
-        }
+    fn fuzzy_indices_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy_chars(choice, pattern, true)
+    }
 
-        Some(adjust_score(score, num_choice_chars))
+    fn fuzzy_match_chars(&self, choice: &[char], pattern: &[char]) -> Option<ScoreType> {
+        self.fuzzy_chars(choice, pattern, false)
+            .map(|(score, _)| score)
+    }
+
+    fn fuzzy_indices_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy_bytes(choice, pattern, true)
+    }
+
+    fn fuzzy_match_bytes(&self, choice: &[u8], pattern: &[u8]) -> Option<ScoreType> {
+        self.fuzzy_bytes(choice, pattern, false)
+            .map(|(score, _)| score)
     }
 }
 
@@ -225,11 +612,14 @@ pub fn fuzzy_match(line: &str, pattern: &str) -> Option<ScoreType> {
 
 // checkout https://github.com/llvm-mirror/clang-tools-extra/blob/master/clangd/FuzzyMatch.cpp
 // for the description
-fn build_graph(
-    line: &[char],
-    pattern: &[char],
+fn build_graph<C: Copy + Into<char>>(
+    line: &[C],
+    pattern: &[C],
     compressed: bool,
     case_sensitive: bool,
+    case_folding: CaseFolding,
+    config: &ClangdScoreConfig,
+    classifier: &dyn CharClassifier,
 ) -> Vec<Vec<Score>> {
     let num_line_chars = line.len();
     let num_pattern_chars = pattern.len();
@@ -243,10 +633,13 @@ fn build_graph(
 
     dp[0][0].miss_score = 0;
 
+    let line_classes = classify(line, classifier);
+    let pattern_classes = classify(pattern, classifier);
+
     // first line
-    for (idx, &ch) in line.iter().enumerate() {
+    for (idx, &(ch_type, _)) in line_classes.iter().enumerate() {
         dp[0][idx + 1] = Score {
-            miss_score: dp[0][idx].miss_score - skip_penalty(idx, ch, Action::Miss),
+            miss_score: dp[0][idx].miss_score - skip_penalty(idx, ch_type, Action::Miss, config),
             last_action_miss: Action::Miss,
             match_score: AWFUL_SCORE,
             last_action_match: Action::Miss,
@@ -254,8 +647,9 @@ fn build_graph(
     }
 
     // build the matrix
-    let mut pat_prev_ch = '\0';
     for (pat_idx, &pat_ch) in pattern.iter().enumerate() {
+        let pat_ch: char = pat_ch.into();
+        let (_, pat_role) = pattern_classes[pat_idx];
         let current_row_idx = if compressed {
             (pat_idx + 1) & 1
         } else {
@@ -263,21 +657,22 @@ fn build_graph(
         };
         let prev_row_idx = if compressed { pat_idx & 1 } else { pat_idx };
 
-        let mut line_prev_ch = '\0';
         for (line_idx, &line_ch) in line.iter().enumerate() {
             if line_idx < pat_idx {
-                line_prev_ch = line_ch;
                 continue;
             }
 
+            let line_ch: char = line_ch.into();
+            let (line_type, line_role) = line_classes[line_idx];
+
             // what if we skip current line character?
             // we need to calculate the cases where the pre line character is matched/missed
             let pre_miss = &dp[current_row_idx][line_idx];
             let mut match_miss_score = pre_miss.match_score;
             let mut miss_miss_score = pre_miss.miss_score;
             if pat_idx < num_pattern_chars - 1 {
-                match_miss_score -= skip_penalty(line_idx, line_ch, Action::Match);
-                miss_miss_score -= skip_penalty(line_idx, line_ch, Action::Miss);
+                match_miss_score -= skip_penalty(line_idx, line_type, Action::Match, config);
+                miss_miss_score -= skip_penalty(line_idx, line_type, Action::Miss, config);
             }
 
             let (miss_score, last_action_miss) = if match_miss_score > miss_miss_score {
@@ -289,31 +684,33 @@ fn build_graph(
             // what if we want to match current line character?
             // so we need to calculate the cases where the pre pattern character is matched/missed
             let pre_match = &dp[prev_row_idx][line_idx];
-            let match_match_score = if allow_match(pat_ch, line_ch, case_sensitive) {
+            let match_match_score = if allow_match(pat_ch, line_ch, case_sensitive, case_folding) {
                 pre_match.match_score
                     + match_bonus(
                         pat_idx,
                         pat_ch,
-                        pat_prev_ch,
+                        pat_role,
                         line_idx,
                         line_ch,
-                        line_prev_ch,
+                        line_role,
                         Action::Match,
+                        config,
                     )
             } else {
                 AWFUL_SCORE
             };
 
-            let miss_match_score = if allow_match(pat_ch, line_ch, case_sensitive) {
+            let miss_match_score = if allow_match(pat_ch, line_ch, case_sensitive, case_folding) {
                 pre_match.miss_score
                     + match_bonus(
                         pat_idx,
                         pat_ch,
-                        pat_prev_ch,
+                        pat_role,
                         line_idx,
                         line_ch,
-                        line_prev_ch,
+                        line_role,
                         Action::Match,
+                        config,
                     )
             } else {
                 AWFUL_SCORE
@@ -331,19 +728,73 @@ fn build_graph(
                 match_score,
                 last_action_match,
             };
+        }
+    }
 
-            line_prev_ch = line_ch;
+    dp
+}
+
+/// Search backwards for the best score and the matched indices, the graph should not be
+/// compressed.
+fn best_path(
+    dp: &[Vec<Score>],
+    num_pattern_chars: usize,
+    num_choice_chars: usize,
+) -> (ScoreType, Vec<IndexType>) {
+    let mut indices_reverse = Vec::with_capacity(num_pattern_chars);
+    let cell = dp[num_pattern_chars][num_choice_chars];
+
+    let (mut last_action, score) = if cell.match_score > cell.miss_score {
+        (Action::Match, cell.match_score)
+    } else {
+        (Action::Miss, cell.miss_score)
+    };
+
+    let mut row = num_pattern_chars;
+    let mut col = num_choice_chars;
+
+    while row > 0 || col > 0 {
+        if last_action == Action::Match {
+            indices_reverse.push((col - 1) as IndexType);
         }
 
-        pat_prev_ch = pat_ch;
+        let cell = &dp[row][col];
+        if last_action == Action::Match {
+            last_action = cell.last_action_match;
+            row -= 1;
+            col -= 1;
+        } else {
+            last_action = cell.last_action_miss;
+            col -= 1;
+        }
     }
 
-    dp
+    indices_reverse.reverse();
+    (score, indices_reverse)
+}
+
+/// Classify the characters, return the type and the role of each of them
+fn classify<C: Copy + Into<char>>(
+    chars: &[C],
+    classifier: &dyn CharClassifier,
+) -> Vec<(CharType, CharRole)> {
+    let mut prev_type = CharType::Empty;
+    chars
+        .iter()
+        .map(|&ch| {
+            let ch_type = classifier.char_type(ch.into());
+            let role = char_role(prev_type, ch_type);
+            prev_type = ch_type;
+            (ch_type, role)
+        })
+        .collect()
 }
 
-fn adjust_score(score: ScoreType, num_line_chars: usize) -> ScoreType {
+fn adjust_score(score: ScoreType, num_line_chars: usize, config: &ClangdScoreConfig) -> ScoreType {
     // line width will affect 10 scores
-    score - (((num_line_chars + 1) as f64).ln().floor() as ScoreType)
+    score
+        + ScoreType::from(config.penalty_length)
+            * (((num_line_chars + 1) as f64).ln().floor() as ScoreType)
 }
 
 const AWFUL_SCORE: ScoreType = -(1 << 30);
@@ -373,74 +824,158 @@ impl Default for Score {
     }
 }
 
-fn skip_penalty(_ch_idx: usize, ch: char, last_action: Action) -> ScoreType {
-    let mut score = 1;
+fn skip_penalty(
+    ch_idx: usize,
+    ch_type: CharType,
+    last_action: Action,
+    config: &ClangdScoreConfig,
+) -> ScoreType {
+    let mut score = 0;
+    skip_contributions(ch_idx, ch_type, last_action, config, |_, penalty| {
+        score -= penalty
+    });
+    ScoreType::from(score)
+}
+
+/// Report the parts of the (negative) score of skipping a character to `add`
+fn skip_contributions(
+    _ch_idx: usize,
+    ch_type: CharType,
+    last_action: Action,
+    config: &ClangdScoreConfig,
+    mut add: impl FnMut(ScoreKind, i32),
+) {
+    add(ScoreKind::GapExtension, config.gap_skip);
     if last_action == Action::Match {
         // Non-consecutive match.
-        score += 3;
+        add(ScoreKind::SkipAfterMatch, config.gap_after_match);
     }
 
-    if char_type_of(ch) == CharType::NonWord {
+    if !ch_type.is_word() {
         // skip separator
-        score += 6;
+        add(ScoreKind::SkipSeparator, config.gap_separator);
     }
-
-    score
 }
 
-fn allow_match(pat_ch: char, line_ch: char, case_sensitive: bool) -> bool {
-    char_equal(pat_ch, line_ch, case_sensitive)
+fn allow_match(
+    pat_ch: char,
+    line_ch: char,
+    case_sensitive: bool,
+    case_folding: CaseFolding,
+) -> bool {
+    char_equal(line_ch, pat_ch, case_sensitive, case_folding)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn match_bonus(
     pat_idx: usize,
     pat_ch: char,
-    pat_prev_ch: char,
+    pat_role: CharRole,
     line_idx: usize,
     line_ch: char,
-    line_prev_ch: char,
+    line_role: CharRole,
     last_action: Action,
+    config: &ClangdScoreConfig,
 ) -> ScoreType {
-    let mut score = 10;
-    let pat_role = char_role(pat_prev_ch, pat_ch);
-    let line_role = char_role(line_prev_ch, line_ch);
+    let mut score = 0;
+    match_contributions(
+        pat_idx,
+        pat_ch,
+        pat_role,
+        line_idx,
+        line_ch,
+        line_role,
+        last_action,
+        config,
+        |_, bonus| score += bonus,
+    );
+    ScoreType::from(score)
+}
+
+/// Report the parts of the score of matching a character to `add`
+#[allow(clippy::too_many_arguments)]
+fn match_contributions(
+    pat_idx: usize,
+    pat_ch: char,
+    pat_role: CharRole,
+    line_idx: usize,
+    line_ch: char,
+    line_role: CharRole,
+    last_action: Action,
+    config: &ClangdScoreConfig,
+    mut add: impl FnMut(ScoreKind, i32),
+) {
+    add(ScoreKind::Match, config.score_match);
 
     // Bonus: pattern so far is a (case-insensitive) prefix of the word.
     if pat_idx == line_idx {
-        score += 10;
+        add(ScoreKind::Prefix, config.bonus_prefix);
     }
 
     // Bonus: case match
     if pat_ch == line_ch {
-        score += 8;
+        add(ScoreKind::CaseMatch, config.bonus_case_match);
     }
 
     // Bonus: match header
     if line_role == CharRole::Head {
-        score += 9;
+        add(ScoreKind::Head, config.bonus_head);
     }
 
     // Bonus: a Head in the pattern aligns with one in the word.
     if pat_role == CharRole::Head && line_role == CharRole::Head {
-        score += 10;
+        add(ScoreKind::HeadAligned, config.bonus_head_aligned);
     }
 
     // Penalty: matching inside a segment (and previous char wasn't matched).
     if line_role == CharRole::Tail && pat_idx > 0 && last_action == Action::Miss {
-        score -= 30;
+        add(ScoreKind::TailAfterSkip, config.penalty_tail_after_skip);
     }
 
     // Penalty: a Head in the pattern matches in the middle of a word segment.
     if pat_role == CharRole::Head && line_role == CharRole::Tail {
-        score -= 10;
+        add(ScoreKind::HeadInTail, config.penalty_head_in_tail);
     }
 
     // Penalty: matching the first pattern character in the middle of a segment.
     if pat_idx == 0 && line_role == CharRole::Tail {
-        score -= 40;
+        add(
+            ScoreKind::FirstCharInTail,
+            config.penalty_first_char_in_tail,
+        );
     }
+}
 
-    score
+#[allow(dead_code)]
+fn print_dp(line: &str, pattern: &str, dp: &[Vec<Score>]) {
+    let num_line_chars = line.chars().count();
+    let num_pattern_chars = pattern.chars().count();
+
+    print!("\t");
+    for (idx, ch) in line.chars().enumerate() {
+        print!("\t\t{}/{}", idx + 1, ch);
+    }
+
+    for (row_num, row) in dp.iter().enumerate().take(num_pattern_chars + 1) {
+        print!("\n{}\t", row_num);
+        for cell in row.iter().take(num_line_chars + 1) {
+            print!(
+                "({},{})/({},{})\t",
+                cell.miss_score,
+                if cell.last_action_miss == Action::Miss {
+                    'X'
+                } else {
+                    'O'
+                },
+                cell.match_score,
+                if cell.last_action_match == Action::Miss {
+                    'X'
+                } else {
+                    'O'
+                }
+            );
+        }
+    }
 }
 
 #[cfg(test)]
@@ -468,6 +1003,211 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_case_folding() {
+        let matcher = ClangdMatcher::default();
+        assert!(matcher.fuzzy_match("ÄBC", "äbc").is_none());
+
+        let matcher = ClangdMatcher::default().case_folding(CaseFolding::Simple);
+        assert!(matcher.fuzzy_match("ÄBC", "äbc").is_some());
+        assert!(matcher.fuzzy_match("ΣΟΦΟΣ", "σοφος").is_some());
+        assert!(matcher.fuzzy_match("straße", "strasse").is_none());
+
+        let matcher = ClangdMatcher::default()
+            .smart_case()
+            .case_folding(CaseFolding::Simple);
+        assert!(matcher.fuzzy_match("äbc", "Äbc").is_none());
+        assert!(matcher.fuzzy_match("Äbc", "Äbc").is_some());
+
+        let matcher = ClangdMatcher::default().case_folding(CaseFolding::Full);
+        assert!(matcher.fuzzy_match("straße", "strasse").is_some());
+        assert!(matcher.fuzzy_match("STRASSE", "straße").is_some());
+        let (_, indices) = matcher.fuzzy_indices("Straße", "sse").unwrap();
+        assert_eq!(wrap_matches("Straße", &indices), "[S]tra[ß][e]");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let matcher = ClangdMatcher::default();
+        assert!(matcher.fuzzy_match("résumé", "resume").is_none());
+
+        let matcher = ClangdMatcher::default().normalize(true);
+        assert!(matcher.fuzzy_match("résumé", "resume").is_some());
+        assert!(matcher.fuzzy_match("Zürich", "zurich").is_some());
+        let (_, indices) = matcher
+            .fuzzy_indices("re\u{301}sume\u{301}", "esm")
+            .unwrap();
+        assert_eq!(
+            wrap_matches("re\u{301}sume\u{301}", &indices),
+            "r[e]\u{301}[s]u[m]e\u{301}"
+        );
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let matcher = ClangdMatcher::default().graphemes(true);
+        let result = matcher.fuzzy_result("cafe\u{301} bar", "eb").unwrap();
+        assert_eq!(result.indices, [3, 5]);
+        assert_eq!(result.ranges, [3..6, 7..8]);
+
+        let result = matcher.fuzzy_result("👍🏽 ok", "👍o").unwrap();
+        assert_eq!(result.indices, [0, 2]);
+        assert_eq!(result.ranges, [0..8, 9..10]);
+
+        let (_, indices) = matcher.fuzzy_indices("a\r\nb", "ab").unwrap();
+        assert_eq!(indices, [0, 2]);
+        assert!(matcher.fuzzy_match("a\r\nb", "ba").is_none());
+
+        // the choices that don't match
+        assert!(matcher.fuzzy_indices("abx", "abc").is_none());
+        assert!(matcher.fuzzy_match("xyz", "abc").is_none());
+        assert!(matcher.fuzzy_indices("cafe\u{301}", "eb").is_none());
+    }
+
+    #[test]
+    fn test_score_config() {
+        let matcher = ClangdMatcher::default().score_config(ClangdScoreConfig::default());
+        assert_eq!(
+            matcher.fuzzy_indices("axbycz", "abc"),
+            fuzzy_indices("axbycz", "abc")
+        );
+
+        // no length penalty
+        let matcher = ClangdMatcher::default().score_config(ClangdScoreConfig {
+            penalty_length: 0,
+            ..ClangdScoreConfig::default()
+        });
+        assert_eq!(
+            matcher.fuzzy_match("map", "ma"),
+            matcher.fuzzy_match("maximum", "ma")
+        );
+
+        // prefer case match over everything else
+        let matcher = ClangdMatcher::default().score_config(ClangdScoreConfig {
+            bonus_case_match: 100,
+            ..ClangdScoreConfig::default()
+        });
+        assert_order(&matcher, "INT", &["INT", "PRINT", "int"]);
+    }
+
+    #[test]
+    fn test_char_classifier() {
+        // treat `_` as part of words, e.g. for snake case identifiers
+        #[derive(Debug)]
+        struct SnakeClassifier;
+        impl CharClassifier for SnakeClassifier {
+            fn char_type(&self, ch: char) -> CharType {
+                match ch {
+                    '_' => CharType::Lower,
+                    _ => ClangdClassifier.char_type(ch),
+                }
+            }
+        }
+
+        let matcher = ClangdMatcher::default().char_classifier(SnakeClassifier);
+        assert!(matcher.fuzzy_match("foo_bar", "bar") < fuzzy_match("foo_bar", "bar"));
+        assert_eq!(
+            matcher.fuzzy_match("fooBar", "bar"),
+            fuzzy_match("fooBar", "bar")
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let matcher = ClangdMatcher::default();
+        let cases = [
+            ("axbycz", "abc"),
+            ("foo_bar", "fb"),
+            ("FooBarBaz", "fbb"),
+            ("src/fuzzy_matcher/clangd.rs", "clangd"),
+            ("aaaaab", "aab"),
+            ("abc", ""),
+        ];
+        for &(choice, pattern) in cases.iter() {
+            let explanation = matcher.explain(choice, pattern).unwrap();
+            let (score, indices) = matcher.fuzzy_indices(choice, pattern).unwrap();
+            assert_eq!(explanation.score, score, "{} / {}", choice, pattern);
+            assert_eq!(explanation.total(), score, "{}", explanation);
+            assert_eq!(explanation.indices, indices);
+        }
+        assert!(matcher.explain("abc", "abx").is_none());
+
+        let explanation = matcher.explain("a_b", "b").unwrap();
+        assert_eq!(explanation.matches.len(), 1);
+        assert!(explanation.matches[0]
+            .contributions
+            .iter()
+            .any(|c| c.kind == ScoreKind::SkipSeparator));
+        assert_eq!(explanation.adjustments[0].kind, ScoreKind::Length);
+    }
+
+    #[test]
+    fn test_ascii_same_as_chars() {
+        let choices = [
+            "axbycz",
+            "fooBarBaz",
+            "src/clangd.rs",
+            "a_b-c d",
+            "AbCABC",
+            "abc",
+        ];
+        let patterns = ["abc", "fbb", "sr", "ABC", "b-c", "a", ""];
+        let matchers = [
+            ClangdMatcher::default(),
+            ClangdMatcher::default().smart_case(),
+            ClangdMatcher::default().respect_case(),
+        ];
+        for matcher in matchers.iter() {
+            for choice in choices.iter() {
+                for pattern in patterns.iter() {
+                    let case_sensitive = matcher.is_case_sensitive(pattern);
+                    let choice_chars: Vec<char> = choice.chars().collect();
+                    let pattern_chars: Vec<char> = pattern.chars().collect();
+                    let matched = cheap_matches(
+                        &choice_chars,
+                        &pattern_chars,
+                        case_sensitive,
+                        matcher.case_folding,
+                    )
+                    .is_some();
+                    let prefiltered = may_match(
+                        choice,
+                        pattern,
+                        case_sensitive,
+                        matcher.case_folding,
+                        matcher.normalize,
+                    );
+                    assert_eq!(prefiltered, matched);
+                    if !matched {
+                        continue;
+                    }
+
+                    for &with_pos in [true, false].iter() {
+                        assert_eq!(
+                            matcher.fuzzy_atoms(
+                                choice.as_bytes(),
+                                pattern.as_bytes(),
+                                case_sensitive,
+                                with_pos
+                            ),
+                            matcher.fuzzy_atoms(
+                                &choice_chars,
+                                &pattern_chars,
+                                case_sensitive,
+                                with_pos
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        // drops the buffers after matching
+        let matcher = ClangdMatcher::default().use_cache(false);
+        let (_, indices) = matcher.fuzzy_indices("a世b界c", "abc").unwrap();
+        assert_eq!(indices, vec![0, 2, 4]);
+    }
+
     #[test]
     fn test_match_quality() {
         let matcher = ClangdMatcher::default();
@@ -506,35 +1246,3 @@ mod tests {
         assert_order(&matcher, "Int", &["int", "INT", "PRINT"]);
     }
 }
-
-#[allow(dead_code)]
-fn print_dp(line: &str, pattern: &str, dp: &[Vec<Score>]) {
-    let num_line_chars = line.chars().count();
-    let num_pattern_chars = pattern.chars().count();
-
-    print!("\t");
-    for (idx, ch) in line.chars().enumerate() {
-        print!("\t\t{}/{}", idx + 1, ch);
-    }
-
-    for (row_num, row) in dp.iter().enumerate().take(num_pattern_chars + 1) {
-        print!("\n{}\t", row_num);
-        for cell in row.iter().take(num_line_chars + 1) {
-            print!(
-                "({},{})/({},{})\t",
-                cell.miss_score,
-                if cell.last_action_miss == Action::Miss {
-                    'X'
-                } else {
-                    'O'
-                },
-                cell.match_score,
-                if cell.last_action_match == Action::Miss {
-                    'X'
-                } else {
-                    'O'
-                }
-            );
-        }
-    }
-}
@@ -0,0 +1,146 @@
+//! Classify characters into types, the word boundaries (e.g. head of a word, camel case) are
+//! determined by the types of a character and its previous one, which in turn decide the
+//! bonuses of matching a character.
+//!
+//! The default classifications of `SkimMatcherV2` and `ClangdMatcher` are `SkimClassifier` and
+//! `ClangdClassifier`, customize them by implementing `CharClassifier`, e.g. to treat `-` as a
+//! hard separator for kebab-case names:
+//!
+//! ```edition2018
+//! use fuzzy_matcher::classifier::{CharClassifier, CharType, SkimClassifier};
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! #[derive(Debug)]
+//! struct KebabClassifier;
+//!
+//! impl CharClassifier for KebabClassifier {
+//!     fn char_type(&self, ch: char) -> CharType {
+//!         match ch {
+//!             '-' => CharType::HardSep,
+//!             _ => SkimClassifier.char_type(ch),
+//!         }
+//!     }
+//! }
+//!
+//! let matcher = SkimMatcherV2::default().char_classifier(KebabClassifier);
+//! ```
+
+use std::fmt::Debug;
+
+/// We categorize characters into types:
+///
+/// - Empty(E): the start of string
+/// - Upper(U): the ascii upper case
+/// - lower(L): the ascii lower case & other unicode characters
+/// - number(N): ascii number
+/// - hard separator(S): clearly separate the content: ` ` `/` `\` `|` `(` `) `[` `]` `{` `}`
+/// - soft separator(s): other ascii punctuation, e.g. `!` `"` `#` `$`, ...
+///
+/// Note that the descriptions above are the classification of `SkimClassifier`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CharType {
+    Empty,
+    Upper,
+    Lower,
+    Number,
+    HardSep,
+    SoftSep,
+}
+
+impl CharType {
+    /// Empty and separators are not part of a word
+    pub fn is_word(self) -> bool {
+        matches!(self, CharType::Upper | CharType::Lower | CharType::Number)
+    }
+}
+
+/// Decide the type of a character.
+///
+/// The start of string is always treated as `CharType::Empty` by the matchers, so it is not
+/// necessary to return `Empty` for any character.
+pub trait CharClassifier: Send + Sync + Debug {
+    fn char_type(&self, ch: char) -> CharType;
+}
+
+/// The classification used by `SkimMatcherV2`, only ASCII characters are considered, other
+/// characters are all treated as lower case.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SkimClassifier;
+
+impl CharClassifier for SkimClassifier {
+    fn char_type(&self, ch: char) -> CharType {
+        match ch {
+            '\0' => CharType::Empty,
+            ' ' | '/' | '\\' | '|' | '(' | ')' | '[' | ']' | '{' | '}' => CharType::HardSep,
+            '!'..='\'' | '*'..='.' | ':'..='@' | '^'..='`' | '~' => CharType::SoftSep,
+            '0'..='9' => CharType::Number,
+            'A'..='Z' => CharType::Upper,
+            _ => CharType::Lower,
+        }
+    }
+}
+
+/// The classification used by `ClangdMatcher`, based on the unicode properties of characters.
+/// Characters that are not letters or numbers are all soft separators.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ClangdClassifier;
+
+impl CharClassifier for ClangdClassifier {
+    fn char_type(&self, ch: char) -> CharType {
+        if ch == '\0' {
+            CharType::Empty
+        } else if ch.is_lowercase() {
+            CharType::Lower
+        } else if ch.is_uppercase() {
+            CharType::Upper
+        } else if ch.is_numeric() {
+            CharType::Number
+        } else {
+            CharType::SoftSep
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skim_classifier() {
+        let types: Vec<CharType> = "aZ5 -é"
+            .chars()
+            .map(|ch| SkimClassifier.char_type(ch))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                CharType::Lower,
+                CharType::Upper,
+                CharType::Number,
+                CharType::HardSep,
+                CharType::SoftSep,
+                CharType::Lower,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clangd_classifier() {
+        let types: Vec<CharType> = "aZ5 -É世"
+            .chars()
+            .map(|ch| ClangdClassifier.char_type(ch))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                CharType::Lower,
+                CharType::Upper,
+                CharType::Number,
+                CharType::SoftSep,
+                CharType::SoftSep,
+                CharType::Upper,
+                CharType::SoftSep,
+            ]
+        );
+    }
+}
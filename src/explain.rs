@@ -0,0 +1,194 @@
+//! Break the score of a match down into the contributions of every matched character, so that
+//! it's possible to tell why a choice ranks above another one.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::explain::ScoreKind;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = SkimMatcherV2::default();
+//! let explanation = matcher.explain("foo_bar", "fb").unwrap();
+//!
+//! assert_eq!(explanation.indices, [0, 4]);
+//! assert_eq!(explanation.total(), explanation.score);
+//! assert!(explanation.matches[1]
+//!     .contributions
+//!     .iter()
+//!     .any(|c| c.kind == ScoreKind::Break));
+//! println!("{}", explanation);
+//! ```
+
+use std::fmt;
+
+use crate::{IndexType, ScoreType};
+
+/// What a part of the score is given for
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum ScoreKind {
+    /// base score of a matched character
+    Match,
+    /// bonus for matching the characters of the same case
+    CaseMatch,
+    /// penalty for matching the characters of different cases
+    CaseMismatch,
+    /// bonus for the pattern so far being a prefix of the choice
+    Prefix,
+    /// bonus for matching the head of a word
+    Head,
+    /// bonus for a head in the pattern matching a head in the choice
+    HeadAligned,
+    /// bonus for matching the upper case character of a camel case word
+    Camel,
+    /// bonus for matching the character after a soft separator
+    Break,
+    /// bonus for matching right after the previous matched character
+    Consecutive,
+    /// penalty for matching in the middle of a word right after skipped characters
+    TailAfterSkip,
+    /// penalty for a head in the pattern matching in the middle of a word
+    HeadInTail,
+    /// penalty for matching the first pattern character in the middle of a word
+    FirstCharInTail,
+    /// penalty for opening a gap between the matched characters
+    GapStart,
+    /// penalty for the (skipped) characters in a gap
+    GapExtension,
+    /// extra penalty for skipping a character right after a match
+    SkipAfterMatch,
+    /// extra penalty for skipping a separator
+    SkipSeparator,
+    /// adjustment by the length of the choice
+    Length,
+    /// penalty for the typos in the pattern
+    Typo,
+}
+
+impl fmt::Display for ScoreKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScoreKind::Match => "match",
+            ScoreKind::CaseMatch => "case match",
+            ScoreKind::CaseMismatch => "case mismatch",
+            ScoreKind::Prefix => "prefix",
+            ScoreKind::Head => "head",
+            ScoreKind::HeadAligned => "head aligned",
+            ScoreKind::Camel => "camel",
+            ScoreKind::Break => "break",
+            ScoreKind::Consecutive => "consecutive",
+            ScoreKind::TailAfterSkip => "tail after skip",
+            ScoreKind::HeadInTail => "head in tail",
+            ScoreKind::FirstCharInTail => "first char in tail",
+            ScoreKind::GapStart => "gap start",
+            ScoreKind::GapExtension => "gap extension",
+            ScoreKind::SkipAfterMatch => "skip after match",
+            ScoreKind::SkipSeparator => "skip separator",
+            ScoreKind::Length => "length",
+            ScoreKind::Typo => "typo",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A part of the score
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct Contribution {
+    pub kind: ScoreKind,
+    pub score: ScoreType,
+}
+
+/// The contributions of a matched character. The penalties of a gap are attributed to the
+/// character matched right after it.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct MatchedChar {
+    /// index of the character in the choice
+    pub index: IndexType,
+    /// the matched character, after normalization and case folding expansion if enabled
+    pub ch: char,
+    pub contributions: Vec<Contribution>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Explanation {
+    /// the score, same as the one returned by `fuzzy_match`
+    pub score: ScoreType,
+    /// indices of the matched characters, same as the ones returned by `fuzzy_indices`
+    pub indices: Vec<IndexType>,
+    /// one for every character of the pattern, in the order of the pattern, or of the variant
+    /// of it that matches with typos
+    pub matches: Vec<MatchedChar>,
+    /// contributions that belong to the choice as a whole, e.g. its length or the typos
+    pub adjustments: Vec<Contribution>,
+}
+
+impl Explanation {
+    /// The sum of all the contributions, which equals to `score`
+    pub fn total(&self) -> ScoreType {
+        self.matches
+            .iter()
+            .flat_map(|matched| matched.contributions.iter())
+            .chain(self.adjustments.iter())
+            .map(|contribution| contribution.score)
+            .sum()
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "score: {}", self.score)?;
+        for matched in self.matches.iter() {
+            write!(f, "  {:?} at {}:", matched.ch, matched.index)?;
+            write_contributions(f, &matched.contributions)?;
+            writeln!(f)?;
+        }
+        if !self.adjustments.is_empty() {
+            write!(f, "  choice:")?;
+            write_contributions(f, &self.adjustments)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_contributions(f: &mut fmt::Formatter, contributions: &[Contribution]) -> fmt::Result {
+    for (idx, contribution) in contributions.iter().enumerate() {
+        let sep = if idx == 0 { " " } else { ", " };
+        write!(f, "{}{} {:+}", sep, contribution.kind, contribution.score)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let explanation = Explanation {
+            score: 20,
+            indices: vec![2],
+            matches: vec![MatchedChar {
+                index: 2,
+                ch: 'b',
+                contributions: vec![
+                    Contribution {
+                        kind: ScoreKind::Match,
+                        score: 16,
+                    },
+                    Contribution {
+                        kind: ScoreKind::GapStart,
+                        score: -3,
+                    },
+                ],
+            }],
+            adjustments: vec![Contribution {
+                kind: ScoreKind::Length,
+                score: 7,
+            }],
+        };
+        assert_eq!(explanation.total(), 20);
+        assert_eq!(
+            explanation.to_string(),
+            "score: 20\n  'b' at 2: match +16, gap start -3\n  choice: length +7\n"
+        );
+    }
+}
@@ -0,0 +1,572 @@
+//! Extended search syntax on top of any `FuzzyMatcher`, just like the one used by fzf and skim.
+//!
+//! | Token     | Match type                 | Description                          |
+//! |-----------|----------------------------|--------------------------------------|
+//! | `sbtrkt`  | fuzzy-match                | Items that match `sbtrkt`            |
+//! | `'wild`   | exact-match (quoted)       | Items that include `wild`            |
+//! | `^music`  | prefix-exact-match         | Items that start with `music`        |
+//! | `.mp3$`   | suffix-exact-match         | Items that end with `.mp3`           |
+//! | `^abc$`   | equal-match                | Items that are exactly `abc`         |
+//! | `!fire`   | inverse-exact-match        | Items that do not include `fire`     |
+//! | `!^music` | inverse-prefix-exact-match | Items that do not start with `music` |
+//! | `!.mp3$`  | inverse-suffix-exact-match | Items that do not end with `.mp3`    |
+//! | `!'fire`  | inverse-fuzzy-match        | Items that do not match `fire`       |
+//!
+//! Terms separated by spaces are AND-ed, a single `|` between terms makes them an OR group.
+//! A literal space could be written as `\ `.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::extended::ExtendedMatcher;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+//! assert!(matcher.fuzzy_match("src/main.rs", "^src .rs$").is_some());
+//! assert!(matcher.fuzzy_match("src/main.rs", "^src !main").is_none());
+//! assert!(matcher.fuzzy_match("src/main.rs", ".go$ | .rs$").is_some());
+//!
+//! let (_score, indices) = matcher.fuzzy_indices("src/main.rs", "^src rs$").unwrap();
+//! assert_eq!(indices, [0, 1, 2, 9, 10]);
+//! ```
+
+use std::cmp::max;
+
+use crate::util::{
+    char_equal, contains_upper, extend_units, map_original_indices, transform_chars,
+};
+use crate::{CaseFolding, FuzzyMatcher, IndexType, ScoreType};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum TermType {
+    Fuzzy,
+    Exact,
+    Prefix,
+    Suffix,
+    Equal,
+}
+
+/// A single search term, e.g. `!^music`
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Term {
+    pub term_type: TermType,
+    pub inverse: bool,
+    pub text: String,
+}
+
+impl Term {
+    /// Parse a single token (without spaces) into a term, following fzf's rules.
+    pub fn parse(token: &str) -> Self {
+        let mut text = token;
+        let mut term_type = TermType::Fuzzy;
+        let mut inverse = false;
+
+        if text.len() > 1 && text.starts_with('!') {
+            inverse = true;
+            term_type = TermType::Exact;
+            text = &text[1..];
+        }
+
+        if text.len() > 1 && text.ends_with('$') && !text.ends_with("\\$") {
+            term_type = TermType::Suffix;
+            text = &text[..text.len() - 1];
+        }
+
+        if text.len() > 1 && text.starts_with('\'') {
+            // the quote toggles between fuzzy and exact match
+            if term_type == TermType::Fuzzy {
+                term_type = TermType::Exact;
+            } else if term_type == TermType::Exact {
+                term_type = TermType::Fuzzy;
+            }
+            text = &text[1..];
+        } else if text.len() > 1 && text.starts_with('^') {
+            term_type = if term_type == TermType::Suffix {
+                TermType::Equal
+            } else {
+                TermType::Prefix
+            };
+            text = &text[1..];
+        }
+
+        Self {
+            term_type,
+            inverse,
+            text: text.replace("\\$", "$"),
+        }
+    }
+}
+
+/// A parsed query: a list of OR groups which are AND-ed together.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Query {
+    pub groups: Vec<Vec<Term>>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Self {
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut continue_group = false;
+
+        for token in split_tokens(query) {
+            if token == "|" {
+                continue_group = !groups.is_empty();
+                continue;
+            }
+
+            let term = Term::parse(&token);
+            match groups.last_mut() {
+                Some(group) if continue_group => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+            continue_group = false;
+        }
+
+        Self { groups }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+/// split the query by spaces, `\ ` is treated as a literal space
+fn split_tokens(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&' ') => {
+                token.push(' ');
+                let _ = chars.next();
+            }
+            ' ' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(ch),
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+enum CaseMatching {
+    Respect,
+    Ignore,
+    Smart,
+}
+
+/// Wraps a `FuzzyMatcher` and evaluates the pattern as an extended search query.
+///
+/// Fuzzy terms are delegated to the inner matcher while exact/prefix/suffix/equal terms are
+/// matched here, following the case option of this matcher. Scores of the AND-ed terms are
+/// summed up, an OR group takes the score of its best matching term, and the indices of all
+/// the terms are merged into one sorted list.
+///
+/// If the inner matcher indexes grapheme clusters, so do the exact terms, which match the
+/// clusters by their first characters as well.
+#[derive(Debug)]
+pub struct ExtendedMatcher<M: FuzzyMatcher> {
+    matcher: M,
+    case: CaseMatching,
+    case_folding: CaseFolding,
+    normalize: bool,
+}
+
+impl<M: FuzzyMatcher> ExtendedMatcher<M> {
+    pub fn new(matcher: M) -> Self {
+        Self {
+            matcher,
+            case: CaseMatching::Smart,
+            case_folding: CaseFolding::Ascii,
+            normalize: false,
+        }
+    }
+
+    pub fn ignore_case(mut self) -> Self {
+        self.case = CaseMatching::Ignore;
+        self
+    }
+
+    pub fn smart_case(mut self) -> Self {
+        self.case = CaseMatching::Smart;
+        self
+    }
+
+    pub fn respect_case(mut self) -> Self {
+        self.case = CaseMatching::Respect;
+        self
+    }
+
+    /// Set how characters are folded in case insensitive exact matching, it also decides
+    /// which characters count as upper case for smart case.
+    pub fn case_folding(mut self, case_folding: CaseFolding) -> Self {
+        self.case_folding = case_folding;
+        self
+    }
+
+    /// Match exact terms regardless of the diacritics, e.g. "'resume" matches "résumé".
+    /// Note that fuzzy terms follow the normalization option of the inner matcher.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.matcher
+    }
+
+    fn is_case_sensitive(&self, text: &str) -> bool {
+        match self.case {
+            CaseMatching::Respect => true,
+            CaseMatching::Ignore => false,
+            CaseMatching::Smart => contains_upper(text, self.case_folding),
+        }
+    }
+
+    /// match the choice against an already parsed query, so that the parsing could be reused
+    /// among choices.
+    pub fn query_indices(
+        &self,
+        choice: &str,
+        query: &Query,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let mut score = 0;
+        let mut indices = Vec::new();
+
+        for group in query.groups.iter() {
+            let (group_score, group_indices) = group
+                .iter()
+                .filter_map(|term| self.term_indices(choice, term))
+                .max_by_key(|(score, _)| *score)?;
+            score += group_score;
+            indices.extend(group_indices);
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+        Some((score, indices))
+    }
+
+    fn term_indices(&self, choice: &str, term: &Term) -> Option<(ScoreType, Vec<IndexType>)> {
+        let result = match term.term_type {
+            TermType::Fuzzy => self.matcher.fuzzy_indices(choice, &term.text),
+            _ => self.exact_indices(choice, term),
+        };
+
+        match (term.inverse, result) {
+            (false, result) => result,
+            (true, Some(_)) => None,
+            (true, None) => Some((0, Vec::new())),
+        }
+    }
+
+    /// The units of the text for exact matching and the index of the original unit for each
+    /// of them, see `transform_chars`.
+    fn exact_units(&self, text: &str, case_sensitive: bool) -> (Vec<char>, Option<Vec<usize>>) {
+        let mut units = Vec::new();
+        extend_units(&mut units, text, self.matcher.indexes_graphemes());
+
+        // strip the diacritics if needed, and with full case folding, e.g. "ß" should match
+        // "ss", so expand these characters and match them one by one.
+        let expand_folding = !case_sensitive && self.case_folding == CaseFolding::Full;
+        let original_indices = transform_chars(&mut units, self.normalize, expand_folding);
+        (units, original_indices)
+    }
+
+    /// The best score of a term, the exact terms score their transformed text under the inner
+    /// matcher, or 0 if it doesn't match.
+    fn term_max_score(&self, term: &Term) -> Option<ScoreType> {
+        match (term.inverse, term.term_type) {
+            (true, _) => Some(0),
+            (false, TermType::Fuzzy) => self.matcher.max_score(&term.text),
+            (false, _) => {
+                let (pattern, _) = self.exact_units(&term.text, self.is_case_sensitive(&term.text));
+                let pattern: String = pattern.into_iter().collect();
+                Some(max(0, self.matcher.max_score(&pattern)?))
+            }
+        }
+    }
+
+    fn exact_indices(&self, choice: &str, term: &Term) -> Option<(ScoreType, Vec<IndexType>)> {
+        let case_sensitive = self.is_case_sensitive(&term.text);
+        let (choice, original_indices) = self.exact_units(choice, case_sensitive);
+        let (pattern, _) = self.exact_units(&term.text, case_sensitive);
+
+        if pattern.len() > choice.len() {
+            return None;
+        }
+
+        let matches_at = |start: usize| {
+            choice[start..]
+                .iter()
+                .zip(pattern.iter())
+                .all(|(&c, &p)| char_equal(c, p, case_sensitive, self.case_folding))
+        };
+
+        let last_start = choice.len() - pattern.len();
+        let start = match term.term_type {
+            TermType::Prefix => Some(0).filter(|&s| matches_at(s)),
+            TermType::Suffix => Some(last_start).filter(|&s| matches_at(s)),
+            TermType::Equal => Some(0).filter(|&s| last_start == 0 && matches_at(s)),
+            _ => (0..=last_start).find(|&s| matches_at(s)),
+        }?;
+
+        // score the matched text as if it were the whole choice, so that exact terms are
+        // comparable to the fuzzy terms scored by the inner matcher.
+        let end = start + pattern.len();
+        let matched: String = choice[start..end].iter().collect();
+        let pattern: String = pattern.into_iter().collect();
+        let score = self.matcher.fuzzy_match(&matched, &pattern).unwrap_or(0);
+
+        let mut indices: Vec<IndexType> = (start..end).map(|idx| idx as IndexType).collect();
+        if let Some(original_indices) = original_indices.as_ref() {
+            map_original_indices(&mut indices, original_indices);
+        }
+        Some((score, indices))
+    }
+}
+
+impl<M: FuzzyMatcher> FuzzyMatcher for ExtendedMatcher<M> {
+    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.query_indices(choice, &Query::parse(pattern))
+    }
+
+    /// The sum of the best scores of the groups, a term scores at most the best score of its
+    /// text under the inner matcher, and an inverse term scores 0. None if the inner matcher
+    /// has no bound.
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        let query = Query::parse(pattern);
+        let mut max_score = 0;
+        for group in query.groups.iter() {
+            let mut group_max = None;
+            for term in group.iter() {
+                group_max = max(group_max, Some(self.term_max_score(term)?));
+            }
+            max_score += group_max.unwrap_or(0);
+        }
+        Some(max_score)
+    }
+
+    fn indexes_graphemes(&self) -> bool {
+        self.matcher.indexes_graphemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clangd::ClangdMatcher;
+    use crate::skim::SkimMatcherV2;
+    use crate::util::wrap_matches;
+
+    fn term(term_type: TermType, inverse: bool, text: &str) -> Term {
+        Term {
+            term_type,
+            inverse,
+            text: text.to_string(),
+        }
+    }
+
+    fn wrap_query(matcher: &dyn FuzzyMatcher, line: &str, pattern: &str) -> Option<String> {
+        let (_score, indices) = matcher.fuzzy_indices(line, pattern)?;
+        Some(wrap_matches(line, &indices))
+    }
+
+    #[test]
+    fn test_parse_term() {
+        assert_eq!(
+            Term::parse("sbtrkt"),
+            term(TermType::Fuzzy, false, "sbtrkt")
+        );
+        assert_eq!(Term::parse("'wild"), term(TermType::Exact, false, "wild"));
+        assert_eq!(
+            Term::parse("^music"),
+            term(TermType::Prefix, false, "music")
+        );
+        assert_eq!(Term::parse(".mp3$"), term(TermType::Suffix, false, ".mp3"));
+        assert_eq!(Term::parse("^abc$"), term(TermType::Equal, false, "abc"));
+        assert_eq!(Term::parse("!fire"), term(TermType::Exact, true, "fire"));
+        assert_eq!(
+            Term::parse("!^music"),
+            term(TermType::Prefix, true, "music")
+        );
+        assert_eq!(Term::parse("!.mp3$"), term(TermType::Suffix, true, ".mp3"));
+        assert_eq!(Term::parse("!'fire"), term(TermType::Fuzzy, true, "fire"));
+
+        // special characters on their own are matched literally
+        assert_eq!(Term::parse("!"), term(TermType::Fuzzy, false, "!"));
+        assert_eq!(Term::parse("^"), term(TermType::Fuzzy, false, "^"));
+        assert_eq!(Term::parse("$"), term(TermType::Fuzzy, false, "$"));
+        assert_eq!(Term::parse("a\\$"), term(TermType::Fuzzy, false, "a$"));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = Query::parse("^core go$ | rb$ | py$ a\\ b");
+        assert_eq!(
+            query.groups,
+            vec![
+                vec![term(TermType::Prefix, false, "core")],
+                vec![
+                    term(TermType::Suffix, false, "go"),
+                    term(TermType::Suffix, false, "rb"),
+                    term(TermType::Suffix, false, "py"),
+                ],
+                vec![term(TermType::Fuzzy, false, "a b")],
+            ]
+        );
+
+        assert!(Query::parse("").is_empty());
+        assert!(Query::parse("   ").is_empty());
+        assert_eq!(Query::parse("| abc |").groups.len(), 1);
+    }
+
+    #[test]
+    fn test_match_or_not() {
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+        assert_eq!(matcher.fuzzy_match("abc", ""), Some(0));
+        assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "'abc").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "'ycz").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "^axb").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "^xb").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "cz$").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "cy$").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "^axbycz$").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "^axbyc$").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "!xyz").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "!'xyz").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "!^ax").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "!cz$").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "abc !foo").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "abc foo").is_none());
+        assert!(matcher.fuzzy_match("axbycz", "abc foo | ^ax").is_some());
+        assert!(matcher.fuzzy_match("axbycz", "foo | bar").is_none());
+        assert!(matcher.fuzzy_match("a b", "a\\ b").is_some());
+    }
+
+    #[test]
+    fn test_case_option() {
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+        assert!(matcher.fuzzy_match("aBc", "'abc").is_some());
+        assert!(matcher.fuzzy_match("aBc", "'aBc").is_some());
+        assert!(matcher.fuzzy_match("aBc", "'aBC").is_none());
+
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default()).ignore_case();
+        assert!(matcher.fuzzy_match("aBc", "'aBC").is_some());
+
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default()).respect_case();
+        assert!(matcher.fuzzy_match("aBc", "'abc").is_none());
+
+        let matcher =
+            ExtendedMatcher::new(SkimMatcherV2::default()).case_folding(CaseFolding::Full);
+        assert!(matcher.fuzzy_match("Straße", "'strasse").is_some());
+        assert_eq!(
+            wrap_query(&matcher, "Straße", "sse$").unwrap(),
+            "Stra[ß][e]"
+        );
+
+        let matcher =
+            ExtendedMatcher::new(SkimMatcherV2::default().normalize(true)).normalize(true);
+        assert!(matcher.fuzzy_match("Zürich", "^zur").is_some());
+        assert!(matcher.fuzzy_match("résumé.pdf", "resume pdf$").is_some());
+        assert_eq!(
+            wrap_query(&matcher, "re\u{301}sume\u{301}", "'sume").unwrap(),
+            "re\u{301}[s][u][m][e]\u{301}"
+        );
+    }
+
+    #[test]
+    fn test_merged_indices() {
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+        assert_eq!(
+            wrap_query(&matcher, "src/main.rs", "^src rs$").unwrap(),
+            "[s][r][c]/main.[r][s]"
+        );
+        assert_eq!(
+            wrap_query(&matcher, "src/main.rs", "'main mn !test").unwrap(),
+            "src/[m][a][i][n].rs"
+        );
+        assert_eq!(
+            wrap_query(&matcher, "src/main.rs", ".go$ | .rs$").unwrap(),
+            "src/main[.][r][s]"
+        );
+
+        let matcher = ExtendedMatcher::new(ClangdMatcher::default());
+        assert_eq!(
+            wrap_query(&matcher, "src/main.rs", "^src mai").unwrap(),
+            "[s][r][c]/[m][a][i]n.rs"
+        );
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let choice = "cafe\u{301} bar";
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default().graphemes(true));
+        assert!(matcher.indexes_graphemes());
+        // the exact terms are located on the clusters, just like the fuzzy ones
+        assert_eq!(matcher.fuzzy_indices(choice, "'b").unwrap().1, [5]);
+        assert_eq!(matcher.fuzzy_indices(choice, "b").unwrap().1, [5]);
+        assert_eq!(
+            matcher.fuzzy_indices(choice, "ar$ 'fe").unwrap().1,
+            [2, 3, 6, 7]
+        );
+        assert!(matcher.fuzzy_match(choice, "^cafe\u{301}\u{301}").is_some());
+
+        let result = matcher.fuzzy_result(choice, "'e b").unwrap();
+        assert_eq!(result.ranges, [3..6, 7..8]);
+
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+        assert_eq!(matcher.fuzzy_indices(choice, "'b").unwrap().1, [6]);
+    }
+
+    #[test]
+    fn test_combined_score() {
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+        let inner = matcher.inner();
+
+        let single = inner.fuzzy_match("src/main.rs", "mn").unwrap();
+        assert_eq!(matcher.fuzzy_match("src/main.rs", "mn !test"), Some(single));
+
+        let combined = matcher.fuzzy_match("src/main.rs", "mn sr").unwrap();
+        assert_eq!(
+            combined,
+            single + inner.fuzzy_match("src/main.rs", "sr").unwrap()
+        );
+
+        // OR group takes the best score
+        let best = matcher.fuzzy_match("src/main.rs", "mn | zzz | sr").unwrap();
+        assert_eq!(
+            best,
+            std::cmp::max(single, inner.fuzzy_match("src/main.rs", "sr").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_max_score() {
+        let matcher = ExtendedMatcher::new(SkimMatcherV2::default());
+        let inner = matcher.inner();
+        assert_eq!(
+            matcher.max_score("^src rs$ | go$ !test"),
+            Some(inner.max_score("src").unwrap() + inner.max_score("rs").unwrap())
+        );
+        assert_eq!(
+            matcher.fuzzy_match_normalized("src", "^src !test"),
+            inner.fuzzy_match_normalized("src", "src")
+        );
+        let normalized = matcher
+            .fuzzy_match_normalized("src/main.rs", "^src rs$")
+            .unwrap();
+        assert!(normalized > 0.0 && normalized <= 1.0);
+    }
+}
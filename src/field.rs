@@ -0,0 +1,380 @@
+//! Match records of delimited fields, e.g. `name\tdescription\tpath`, on top of any
+//! `FuzzyMatcher`, just like the `--nth` and `--delimiter` options of skim and fzf.
+//!
+//! Fields are numbered from 1, negative numbers count from the end, e.g. `-1` is the last
+//! field. A selection is a comma separated list of fields or ranges of them:
+//!
+//! | Selection | Fields                              |
+//! |-----------|-------------------------------------|
+//! | `2`       | the second field                    |
+//! | `-1`      | the last field                      |
+//! | `2..`     | from the second field to the last   |
+//! | `..3`     | from the first field to the third   |
+//! | `1..-2`   | all but the last field              |
+//! | `1,3`     | the first and the third field       |
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::field::{FieldMatcher, FieldRange};
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = FieldMatcher::new(SkimMatcherV2::default())
+//!     .nth(FieldRange::parse_list("1,3"))
+//!     .field_weight(1, 2.0);
+//!
+//! // the description is not matched
+//! assert!(matcher.fuzzy_match("grep\tprint lines\t/usr/bin/grep", "lines").is_none());
+//!
+//! // indices are the ones in the whole record
+//! let (_score, indices) = matcher.fuzzy_indices("ls\tlist files\t/bin/ls", "bin").unwrap();
+//! assert_eq!(indices, [15, 16, 17]);
+//! ```
+
+use crate::util::count_units;
+use crate::{FuzzyMatcher, IndexType, ScoreType};
+
+/// A field, or a range of fields, numbered from 1, negative numbers count from the end.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum FieldRange {
+    Single(isize),
+    /// inclusive on both ends, `None` means the first or the last field
+    Range(Option<isize>, Option<isize>),
+}
+
+impl FieldRange {
+    /// Parse a single selection, e.g. `2`, `-1`, `2..`, `..3`, return None if it's invalid.
+    pub fn parse(range: &str) -> Option<Self> {
+        let range = range.trim();
+        let parse_bound = |bound: &str| -> Option<Option<isize>> {
+            if bound.is_empty() {
+                Some(None)
+            } else {
+                bound.parse().ok().filter(|&idx: &isize| idx != 0).map(Some)
+            }
+        };
+
+        match range.find("..") {
+            Some(pos) => {
+                let start = parse_bound(&range[..pos])?;
+                let end = parse_bound(&range[pos + 2..])?;
+                Some(FieldRange::Range(start, end))
+            }
+            None => parse_bound(range)?.map(FieldRange::Single),
+        }
+    }
+
+    /// Parse a comma separated list of selections, the invalid ones are ignored.
+    pub fn parse_list(ranges: &str) -> Vec<Self> {
+        ranges.split(',').filter_map(FieldRange::parse).collect()
+    }
+
+    /// The (0 based) indices of the selected fields out of `num_fields` ones.
+    fn indices(&self, num_fields: usize) -> std::ops::Range<usize> {
+        let (start, end) = match *self {
+            FieldRange::Single(idx) => (Some(idx), Some(idx)),
+            FieldRange::Range(start, end) => (start, end),
+        };
+
+        let start = start.map_or(Some(0), |idx| resolve(idx, num_fields));
+        let end = end.map_or(Some(num_fields), |idx| {
+            resolve(idx, num_fields).map(|e| e + 1)
+        });
+        match (start, end) {
+            (Some(start), Some(end)) if start < end => start..end,
+            _ => 0..0,
+        }
+    }
+}
+
+/// Resolve a field number to the index of the field, None if it's out of range.
+fn resolve(idx: isize, num_fields: usize) -> Option<usize> {
+    let num_fields = num_fields as isize;
+    let resolved = if idx > 0 { idx - 1 } else { num_fields + idx };
+    if (0..num_fields).contains(&resolved) {
+        Some(resolved as usize)
+    } else {
+        None
+    }
+}
+
+/// The bonus of a field weighted `weight`, relative to the best score of the pattern
+#[allow(clippy::unnecessary_cast)]
+fn weight_bonus(max_score: ScoreType, weight: f64) -> ScoreType {
+    ((weight - 1.0) * max_score as f64).round() as ScoreType
+}
+
+/// A field of a record
+#[derive(Debug)]
+struct Field<'a> {
+    text: &'a str,
+    /// index of the first unit in the record, a character or a grapheme cluster
+    start: usize,
+}
+
+/// Wraps a `FuzzyMatcher` to match the selected fields of records.
+///
+/// The pattern is matched against every selected field separately, a field weighted `w` gets
+/// `(w - 1)` times the best score of the pattern (see `FuzzyMatcher::max_score`) as a bonus,
+/// and the best field wins. Being additive, the weights keep the order of the matches in a
+/// field even if their scores are negative, while they have no effect if the inner matcher
+/// has no best score. The indices refer to the characters, or the grapheme clusters if the
+/// inner matcher indexes them, of the whole record, so it could be highlighted in place.
+#[derive(Debug)]
+pub struct FieldMatcher<M: FuzzyMatcher> {
+    matcher: M,
+    delimiter: String,
+    nth: Vec<FieldRange>,
+    weights: Vec<(isize, f64)>,
+}
+
+impl<M: FuzzyMatcher> FieldMatcher<M> {
+    pub fn new(matcher: M) -> Self {
+        Self {
+            matcher,
+            delimiter: "\t".to_string(),
+            nth: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+
+    /// The delimiter between fields, defaults to a tab. An empty delimiter makes the whole
+    /// record a single field.
+    pub fn delimiter(mut self, delimiter: &str) -> Self {
+        self.delimiter = delimiter.to_string();
+        self
+    }
+
+    /// Select the fields to match, all the fields are matched if none is selected.
+    pub fn nth(mut self, nth: Vec<FieldRange>) -> Self {
+        self.nth = nth;
+        self
+    }
+
+    /// Weigh the scores of the field by `weight`, the field is numbered as in `FieldRange`.
+    /// Fields are weighted 1.0 by default, and the last weight set for a field wins.
+    pub fn field_weight(mut self, field: isize, weight: f64) -> Self {
+        self.weights.push((field, weight));
+        self
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.matcher
+    }
+
+    fn split_fields<'a>(&self, choice: &'a str) -> Vec<Field<'a>> {
+        if self.delimiter.is_empty() {
+            return vec![Field {
+                text: choice,
+                start: 0,
+            }];
+        }
+
+        let graphemes = self.matcher.indexes_graphemes();
+        let delimiter_len = count_units(&self.delimiter, graphemes, false, false);
+        let mut fields = Vec::new();
+        let mut start = 0;
+        for text in choice.split(self.delimiter.as_str()) {
+            fields.push(Field { text, start });
+            start += count_units(text, graphemes, false, false) + delimiter_len;
+        }
+        fields
+    }
+
+    /// the (0 based) indices of the selected fields, in order and without duplicates
+    fn selected(&self, num_fields: usize) -> Vec<usize> {
+        if self.nth.is_empty() {
+            return (0..num_fields).collect();
+        }
+
+        let mut selected: Vec<usize> = self
+            .nth
+            .iter()
+            .flat_map(|range| range.indices(num_fields))
+            .collect();
+        selected.sort_unstable();
+        selected.dedup();
+        selected
+    }
+
+    fn weight_of(&self, field_idx: usize, num_fields: usize) -> f64 {
+        self.weights
+            .iter()
+            .rev()
+            .find(|&&(field, _)| resolve(field, num_fields) == Some(field_idx))
+            .map_or(1.0, |&(_, weight)| weight)
+    }
+}
+
+impl<M: FuzzyMatcher> FuzzyMatcher for FieldMatcher<M> {
+    #[allow(clippy::unnecessary_cast)]
+    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+        if pattern.is_empty() {
+            return self.matcher.fuzzy_indices(choice, pattern);
+        }
+
+        let max_score = self.matcher.max_score(pattern).unwrap_or(0);
+        let fields = self.split_fields(choice);
+        self.selected(fields.len())
+            .into_iter()
+            .filter_map(|field_idx| {
+                let field = &fields[field_idx];
+                let (score, mut indices) = self.matcher.fuzzy_indices(field.text, pattern)?;
+                let weight = self.weight_of(field_idx, fields.len());
+                for idx in indices.iter_mut() {
+                    *idx += field.start as IndexType;
+                }
+                Some((score + weight_bonus(max_score, weight), indices))
+            })
+            .fold(None, |best, current| match best {
+                // the earlier field wins on ties
+                Some(best) if best.0 >= current.0 => Some(best),
+                _ => Some(current),
+            })
+    }
+
+    /// The best score of the inner matcher with the bonus of the largest weight, as the
+    /// pattern could match any field.
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        let max_score = self.matcher.max_score(pattern)?;
+        let max_weight = self
+            .weights
+            .iter()
+            .fold(1.0, |max_weight: f64, &(_, weight)| max_weight.max(weight));
+        Some(max_score + weight_bonus(max_score, max_weight))
+    }
+
+    fn indexes_graphemes(&self) -> bool {
+        self.matcher.indexes_graphemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clangd::ClangdMatcher;
+    use crate::skim::SkimMatcherV2;
+    use crate::util::{assert_order, wrap_matches};
+
+    fn wrap_fuzzy_match(matcher: &dyn FuzzyMatcher, line: &str, pattern: &str) -> Option<String> {
+        let (_score, indices) = matcher.fuzzy_indices(line, pattern)?;
+        Some(wrap_matches(line, &indices))
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(FieldRange::parse("2"), Some(FieldRange::Single(2)));
+        assert_eq!(FieldRange::parse("-1"), Some(FieldRange::Single(-1)));
+        assert_eq!(
+            FieldRange::parse("2.."),
+            Some(FieldRange::Range(Some(2), None))
+        );
+        assert_eq!(
+            FieldRange::parse("..-2"),
+            Some(FieldRange::Range(None, Some(-2)))
+        );
+        assert_eq!(FieldRange::parse(".."), Some(FieldRange::Range(None, None)));
+        assert_eq!(FieldRange::parse("0"), None);
+        assert_eq!(FieldRange::parse("a"), None);
+        assert_eq!(
+            FieldRange::parse_list("1,x,3"),
+            vec![FieldRange::Single(1), FieldRange::Single(3)]
+        );
+
+        assert_eq!(FieldRange::Single(2).indices(3), 1..2);
+        assert_eq!(FieldRange::Single(-1).indices(3), 2..3);
+        assert_eq!(FieldRange::Single(4).indices(3), 0..0);
+        assert_eq!(FieldRange::Range(Some(2), None).indices(3), 1..3);
+        assert_eq!(FieldRange::Range(None, Some(-2)).indices(3), 0..2);
+        assert_eq!(FieldRange::Range(Some(3), Some(1)).indices(3), 0..0);
+    }
+
+    #[test]
+    fn test_nth() {
+        let matcher = FieldMatcher::new(SkimMatcherV2::default());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "ls\tlist\t/bin/ls", "ls").unwrap(),
+            "[l][s]\tlist\t/bin/ls"
+        );
+        assert!(matcher.fuzzy_match("ls\tlist", "s\tl").is_none());
+
+        let matcher = matcher.nth(FieldRange::parse_list("-1"));
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "ls\tlist\t/bin/ls", "ls").unwrap(),
+            "ls\tlist\t/bin/[l][s]"
+        );
+        assert!(matcher.fuzzy_match("ls\tlist\t/bin/ls", "list").is_none());
+        assert_eq!(matcher.fuzzy_match("ls\tlist\t/bin/ls", ""), Some(0));
+
+        let matcher = FieldMatcher::new(SkimMatcherV2::default())
+            .delimiter("::")
+            .nth(FieldRange::parse_list("2.."));
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "文档::说明::ab", "ab").unwrap(),
+            "文档::说明::[a][b]"
+        );
+        assert!(matcher.fuzzy_match("ab::cd", "ab").is_none());
+        assert!(matcher.fuzzy_match("ab", "ab").is_none());
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let matcher = FieldMatcher::new(SkimMatcherV2::default().graphemes(true));
+        assert!(matcher.indexes_graphemes());
+        let (_, indices) = matcher.fuzzy_indices("x\tcafe\u{301} b", "eb").unwrap();
+        assert_eq!(indices, [5, 7]);
+        let result = matcher.fuzzy_result("x\tcafe\u{301} b", "eb").unwrap();
+        assert_eq!(result.ranges, [5..8, 9..10]);
+
+        // the clusters of the former fields are counted as one
+        let result = matcher
+            .nth(FieldRange::parse_list("2"))
+            .fuzzy_result("e\u{301}\tcafe\u{301} b", "eb")
+            .unwrap();
+        assert_eq!(result.indices, [5, 7]);
+        assert_eq!(result.ranges, [7..10, 11..12]);
+    }
+
+    #[test]
+    fn test_field_weight() {
+        let matcher = FieldMatcher::new(SkimMatcherV2::default());
+        let plain = SkimMatcherV2::default().fuzzy_match("abc", "abc").unwrap();
+        let max_score = SkimMatcherV2::default().max_score("abc").unwrap();
+        assert_eq!(matcher.fuzzy_match("x\tabc", "abc"), Some(plain));
+
+        let matcher = matcher.field_weight(1, 2.0).field_weight(-1, 0.5);
+        assert_eq!(
+            matcher.fuzzy_match("abc\tx", "abc"),
+            Some(plain + max_score)
+        );
+        assert_eq!(
+            matcher.fuzzy_match("x\tabc", "abc"),
+            Some(plain - max_score / 2)
+        );
+        assert_order(&matcher, "abc", &["abc\tx", "x\tabc\ty", "x\tabc"]);
+
+        // the best field wins
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "abc\tabc", "abc").unwrap(),
+            "[a][b][c]\tabc"
+        );
+
+        assert_eq!(matcher.max_score("abc"), Some(max_score * 2));
+        let normalized = matcher.fuzzy_match_normalized("abc\tx", "abc").unwrap();
+        assert!(normalized > matcher.fuzzy_match_normalized("x\tabc", "abc").unwrap());
+        assert!(normalized <= 1.0);
+    }
+
+    #[test]
+    fn test_field_weight_negative() {
+        // clangd scores poor matches below 0, which the weights must not flip
+        let inner = ClangdMatcher::default();
+        assert!(inner.fuzzy_match("a..", "..").unwrap() < 0);
+
+        let matcher = FieldMatcher::new(ClangdMatcher::default()).field_weight(1, 2.0);
+        assert_order(&matcher, "..", &["a..\tx", "x\ta.."]);
+        let matcher = FieldMatcher::new(ClangdMatcher::default()).field_weight(1, 0.5);
+        assert_order(&matcher, "..", &["x\ta..", "a..\tx"]);
+        assert_order(&matcher, "..", &["..\tx", "a..\tx"]);
+    }
+}
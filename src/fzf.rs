@@ -0,0 +1,1053 @@
+//! Port of fzf's v1 algorithm, i.e. its "fast mode".
+//! https://github.com/junegunn/fzf/blob/master/src/algo/algo.go
+//!
+//! The algorithm finds the first occurrence of the pattern by a greedy forward scan, then
+//! shrinks the window by scanning backward from its end, so the result is the shortest match
+//! ending at the first possible position, but not necessarily the best one. The matched window
+//! is scored with fzf's bonus rules.
+//!
+//! fzf's v2 algorithm, which finds the best match, is available as the `fzf_compat` mode of
+//! `SkimMatcherV2`, and `rank::Ranking::fzf` sorts the matched choices in the same order as
+//! fzf.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::fzf::FzfV1Matcher;
+//!
+//! let matcher = FzfV1Matcher::default();
+//!
+//! assert_eq!(None, matcher.fuzzy_match("abc", "abx"));
+//! assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
+//!
+//! // the window is shrunk from "a_ab" to "ab"
+//! let (_score, indices) = matcher.fuzzy_indices("a_ab", "ab").unwrap();
+//! assert_eq!(indices, [2, 3]);
+//! ```
+
+use std::cell::RefCell;
+use std::cmp::max;
+
+use thread_local::ThreadLocal;
+
+use crate::util::{
+    char_equal, cheap_matches, cheap_matches_ascii, contains_upper, contains_upper_atoms,
+    count_units, map_original_indices, may_match, transform_chars,
+};
+use crate::{CaseFolding, FuzzyMatcher, IndexType, ScoreType};
+
+pub(crate) const SCORE_MATCH: i32 = 16;
+pub(crate) const SCORE_GAP_START: i32 = -3;
+pub(crate) const SCORE_GAP_EXTENSION: i32 = -1;
+
+/// We prefer matches at the beginning of a word, but the bonus should not be too great to
+/// prevent the longer acronym matches from always winning over shorter fuzzy matches.
+pub(crate) const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+/// Non-word characters themselves are not that important, but they are given the same bonus
+/// as boundaries so that the consecutive chunks containing them are not penalized.
+pub(crate) const BONUS_NON_WORD: i32 = SCORE_MATCH / 2;
+/// Edge-triggered bonus for matches in camelCase words, and for the numbers after letters.
+pub(crate) const BONUS_CAMEL_123: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+/// Minimum bonus point given to characters in consecutive chunks.
+pub(crate) const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+/// The first character of the pattern gets its bonus multiplied.
+pub(crate) const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+/// Boundaries after white spaces are stronger than the ones after delimiters, which are in
+/// turn stronger than the ones after other non-word characters.
+pub(crate) const BONUS_BOUNDARY_WHITE: i32 = BONUS_BOUNDARY + 2;
+pub(crate) const BONUS_BOUNDARY_DELIMITER: i32 = BONUS_BOUNDARY + 1;
+
+const DELIMITER_CHARS: &str = "/,:;|";
+const WHITE_CHARS: &str = " \t\n\u{0B}\u{0C}\r\u{85}\u{A0}";
+
+/// The character classes of fzf, the order matters: classes after `NonWord` are words.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub(crate) enum CharClass {
+    White,
+    NonWord,
+    Delimiter,
+    Lower,
+    Upper,
+    Letter,
+    Number,
+}
+
+impl CharClass {
+    /// The class before the start of string
+    pub const INITIAL: CharClass = CharClass::White;
+
+    pub fn of(ch: char) -> Self {
+        if ch.is_ascii() {
+            match ch {
+                'a'..='z' => CharClass::Lower,
+                'A'..='Z' => CharClass::Upper,
+                '0'..='9' => CharClass::Number,
+                _ if WHITE_CHARS.contains(ch) => CharClass::White,
+                _ if DELIMITER_CHARS.contains(ch) => CharClass::Delimiter,
+                _ => CharClass::NonWord,
+            }
+        } else if ch.is_lowercase() {
+            CharClass::Lower
+        } else if ch.is_uppercase() {
+            CharClass::Upper
+        } else if ch.is_numeric() {
+            CharClass::Number
+        } else if ch.is_alphabetic() {
+            CharClass::Letter
+        } else if ch.is_whitespace() {
+            CharClass::White
+        } else if DELIMITER_CHARS.contains(ch) {
+            CharClass::Delimiter
+        } else {
+            CharClass::NonWord
+        }
+    }
+}
+
+/// The bonus of matching a character of `class` after a character of `prev_class`
+pub(crate) fn bonus_for(prev_class: CharClass, class: CharClass) -> i32 {
+    if class > CharClass::NonWord {
+        match prev_class {
+            CharClass::White => return BONUS_BOUNDARY_WHITE,
+            CharClass::Delimiter => return BONUS_BOUNDARY_DELIMITER,
+            CharClass::NonWord => return BONUS_BOUNDARY,
+            _ => {}
+        }
+    }
+
+    if (prev_class == CharClass::Lower && class == CharClass::Upper)
+        || (prev_class != CharClass::Number && class == CharClass::Number)
+    {
+        // camelCase letter123
+        return BONUS_CAMEL_123;
+    }
+
+    match class {
+        CharClass::NonWord | CharClass::Delimiter => BONUS_NON_WORD,
+        CharClass::White => BONUS_BOUNDARY_WHITE,
+        _ => 0,
+    }
+}
+
+/// The best score of a pattern of `num_pattern` characters, i.e. every character matched with
+/// the greatest bonus, which the first one gets `BONUS_FIRST_CHAR_MULTIPLIER` times.
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn max_score(num_pattern: usize) -> ScoreType {
+    if num_pattern == 0 {
+        return 0;
+    }
+
+    let max_bonus = max(BONUS_BOUNDARY_WHITE, BONUS_CONSECUTIVE) as ScoreType;
+    num_pattern as ScoreType * (SCORE_MATCH as ScoreType + max_bonus)
+        + max_bonus * (BONUS_FIRST_CHAR_MULTIPLIER - 1) as ScoreType
+}
+
+/// fzf's v1 algorithm: shrink the window found by the forward scan, i.e. `first_match_indices`,
+/// by scanning backward, and score it.
+pub(crate) fn fuzzy_match_v1<C: Copy + Into<char>>(
+    choice: &[C],
+    pattern: &[C],
+    first_match_indices: &[usize],
+    case_sensitive: bool,
+    case_folding: CaseFolding,
+    with_pos: bool,
+) -> (ScoreType, Vec<IndexType>) {
+    let (mut start, end) = match (first_match_indices.first(), first_match_indices.last()) {
+        (Some(&first), Some(&last)) => (first, last + 1),
+        _ => return (0, Vec::new()),
+    };
+
+    // scan backward for the shortest window ending at `end`
+    let mut pattern_iter = pattern.iter().rev().peekable();
+    for idx in (start..end).rev() {
+        match pattern_iter.peek() {
+            Some(&&p) => {
+                if char_equal(choice[idx].into(), p.into(), case_sensitive, case_folding) {
+                    let _ = pattern_iter.next();
+                    start = idx;
+                }
+            }
+            None => break,
+        }
+    }
+
+    calculate_score(
+        choice,
+        pattern,
+        start,
+        end,
+        case_sensitive,
+        case_folding,
+        with_pos,
+    )
+}
+
+/// Score the window `[start, end)` as fzf does, the pattern is matched greedily inside it.
+fn calculate_score<C: Copy + Into<char>>(
+    choice: &[C],
+    pattern: &[C],
+    start: usize,
+    end: usize,
+    case_sensitive: bool,
+    case_folding: CaseFolding,
+    with_pos: bool,
+) -> (ScoreType, Vec<IndexType>) {
+    let mut pos = Vec::new();
+
+    let mut pat_idx = 0;
+    let mut score = 0;
+    let mut in_gap = false;
+    let mut consecutive = 0;
+    let mut first_bonus = 0;
+    let mut prev_class = if start > 0 {
+        CharClass::of(choice[start - 1].into())
+    } else {
+        CharClass::INITIAL
+    };
+
+    for (idx, &ch) in choice.iter().enumerate().take(end).skip(start) {
+        if pat_idx >= pattern.len() {
+            break;
+        }
+
+        let ch: char = ch.into();
+        let class = CharClass::of(ch);
+        if char_equal(ch, pattern[pat_idx].into(), case_sensitive, case_folding) {
+            if with_pos {
+                pos.push(idx as IndexType);
+            }
+
+            score += SCORE_MATCH;
+            let mut bonus = bonus_for(prev_class, class);
+            if consecutive == 0 {
+                first_bonus = bonus;
+            } else {
+                // break consecutive chunk
+                if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                    first_bonus = bonus;
+                }
+                bonus = max(max(bonus, first_bonus), BONUS_CONSECUTIVE);
+            }
+
+            if pat_idx == 0 {
+                score += bonus * BONUS_FIRST_CHAR_MULTIPLIER;
+            } else {
+                score += bonus;
+            }
+
+            in_gap = false;
+            consecutive += 1;
+            pat_idx += 1;
+        } else {
+            score += if in_gap {
+                SCORE_GAP_EXTENSION
+            } else {
+                SCORE_GAP_START
+            };
+            in_gap = true;
+            consecutive = 0;
+            first_bonus = 0;
+        }
+
+        prev_class = class;
+    }
+
+    (score as ScoreType, pos)
+}
+
+/// fzf's v2 algorithm, a modified Smith-Waterman algorithm which finds the best scored match.
+/// `first_match_indices` are the indices found by the forward scan, i.e. `F` in fzf.
+///
+/// Unlike `SkimMatcherV2`, the bonus of a consecutive chunk is decided by its first character,
+/// and on ties the earliest end of the match wins.
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn fuzzy_match_v2<C: Copy + Into<char>>(
+    choice: &[C],
+    pattern: &[C],
+    first_match_indices: &[usize],
+    case_sensitive: bool,
+    case_folding: CaseFolding,
+    with_pos: bool,
+) -> (ScoreType, Vec<IndexType>) {
+    let num_pattern = pattern.len();
+    if num_pattern == 0 || first_match_indices.len() != num_pattern {
+        return (0, Vec::new());
+    }
+
+    let char_eq = |c: C, p: C| char_equal(c.into(), p.into(), case_sensitive, case_folding);
+    let first_pattern_char = pattern[0];
+    let last_pattern_char = pattern[num_pattern - 1];
+
+    // phase 1: calculate the bonus of every position, and the scores of the first row
+    let mut bonuses = Vec::with_capacity(choice.len());
+    let mut first_row = Vec::with_capacity(choice.len());
+    let mut first_row_consecutive = Vec::with_capacity(choice.len());
+    let mut max_score = 0;
+    let mut max_score_pos = 0;
+    let mut last_idx = 0;
+    let mut prev_class = CharClass::INITIAL;
+    let mut prev_score = 0;
+    let mut in_gap = false;
+    for (idx, &ch) in choice.iter().enumerate() {
+        let class = CharClass::of(ch.into());
+        let bonus = bonus_for(prev_class, class);
+        bonuses.push(bonus);
+        prev_class = class;
+
+        if char_eq(ch, last_pattern_char) {
+            last_idx = idx;
+        }
+
+        let score = if char_eq(ch, first_pattern_char) {
+            let score = SCORE_MATCH + bonus * BONUS_FIRST_CHAR_MULTIPLIER;
+            first_row_consecutive.push(1);
+            in_gap = false;
+            if num_pattern == 1 && score > max_score {
+                max_score = score;
+                max_score_pos = idx;
+                if bonus >= BONUS_BOUNDARY {
+                    break;
+                }
+            }
+            score
+        } else {
+            let gap = if in_gap {
+                SCORE_GAP_EXTENSION
+            } else {
+                SCORE_GAP_START
+            };
+            first_row_consecutive.push(0);
+            in_gap = true;
+            max(prev_score + gap, 0)
+        };
+        first_row.push(score);
+        prev_score = score;
+    }
+
+    if num_pattern == 1 {
+        let mut pos = Vec::new();
+        if with_pos {
+            pos.push(max_score_pos as IndexType);
+        }
+        return (max_score as ScoreType, pos);
+    }
+
+    // phase 2: fill in the score matrix `h` and the length of consecutive chunks `c`, only the
+    // columns between the first match and the last occurrence of the last pattern char are kept.
+    let f0 = first_match_indices[0];
+    let width = last_idx - f0 + 1;
+    let mut h = vec![0; width * num_pattern];
+    let mut c = vec![0; width * num_pattern];
+    h[..width].copy_from_slice(&first_row[f0..=last_idx]);
+    c[..width].copy_from_slice(&first_row_consecutive[f0..=last_idx]);
+
+    for (pat_idx, &p_ch) in pattern.iter().enumerate().skip(1) {
+        let f = first_match_indices[pat_idx];
+        let row = pat_idx * width;
+        let mut in_gap = false;
+        for col in f..=last_idx {
+            let cell = row + col - f0;
+            let diag = cell - width - 1;
+
+            let s2 = h[cell - 1]
+                + if in_gap {
+                    SCORE_GAP_EXTENSION
+                } else {
+                    SCORE_GAP_START
+                };
+
+            let mut s1 = 0;
+            let mut consecutive = 0;
+            if char_eq(choice[col], p_ch) {
+                s1 = h[diag] + SCORE_MATCH;
+                let mut bonus = bonuses[col];
+                consecutive = c[diag] + 1;
+                if consecutive > 1 {
+                    let first_bonus = bonuses[col + 1 - consecutive as usize];
+                    if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                        // break consecutive chunk
+                        consecutive = 1;
+                    } else {
+                        bonus = max(bonus, max(BONUS_CONSECUTIVE, first_bonus));
+                    }
+                }
+                if s1 + bonus < s2 {
+                    s1 += bonuses[col];
+                    consecutive = 0;
+                } else {
+                    s1 += bonus;
+                }
+            }
+            c[cell] = consecutive;
+
+            in_gap = s1 < s2;
+            let score = max(max(s1, s2), 0);
+            if pat_idx == num_pattern - 1 && score > max_score {
+                max_score = score;
+                max_score_pos = col;
+            }
+            h[cell] = score;
+        }
+    }
+
+    // phase 3: backtrack the matched indices
+    let mut pos = Vec::new();
+    if with_pos {
+        pos.resize(num_pattern, 0);
+        let mut i = num_pattern - 1;
+        let mut j = max_score_pos;
+        let mut prefer_match = true;
+        loop {
+            let row = i * width;
+            let cell = row + j - f0;
+            let score = h[cell];
+
+            let s1 = if i > 0 && j >= first_match_indices[i] {
+                h[cell - width - 1]
+            } else {
+                0
+            };
+            let s2 = if j > first_match_indices[i] {
+                h[cell - 1]
+            } else {
+                0
+            };
+
+            if score > s1 && (score > s2 || score == s2 && prefer_match) {
+                pos[i] = j as IndexType;
+                if i == 0 {
+                    break;
+                }
+                i -= 1;
+            }
+            prefer_match = c[cell] > 1 || (cell + width + 1 < c.len() && c[cell + width + 1] > 0);
+            j -= 1;
+        }
+    }
+
+    (max_score as ScoreType, pos)
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+enum CaseMatching {
+    Respect,
+    Ignore,
+    Smart,
+}
+
+#[derive(Debug)]
+pub struct FzfV1Matcher {
+    case: CaseMatching,
+    case_folding: CaseFolding,
+    normalize: bool,
+
+    use_cache: bool,
+
+    c_cache: ThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of choice
+    p_cache: ThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of pattern
+}
+
+impl Default for FzfV1Matcher {
+    /// Smart case with unicode simple case folding, same as fzf
+    fn default() -> Self {
+        Self {
+            case: CaseMatching::Smart,
+            case_folding: CaseFolding::Simple,
+            normalize: false,
+            use_cache: true,
+            c_cache: ThreadLocal::new(),
+            p_cache: ThreadLocal::new(),
+        }
+    }
+}
+
+impl FzfV1Matcher {
+    pub fn ignore_case(mut self) -> Self {
+        self.case = CaseMatching::Ignore;
+        self
+    }
+
+    pub fn smart_case(mut self) -> Self {
+        self.case = CaseMatching::Smart;
+        self
+    }
+
+    pub fn respect_case(mut self) -> Self {
+        self.case = CaseMatching::Respect;
+        self
+    }
+
+    /// Set how characters are folded in case insensitive matching, it also decides which
+    /// characters count as upper case for smart case.
+    pub fn case_folding(mut self, case_folding: CaseFolding) -> Self {
+        self.case_folding = case_folding;
+        self
+    }
+
+    /// Match the characters regardless of their diacritics, e.g. "resume" matches "résumé".
+    /// The indices still refer to the characters of the original choice.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    pub fn use_cache(mut self, use_cache: bool) -> Self {
+        self.use_cache = use_cache;
+        self
+    }
+
+    fn is_case_sensitive(&self, pattern: &str) -> bool {
+        match self.case {
+            CaseMatching::Respect => true,
+            CaseMatching::Ignore => false,
+            CaseMatching::Smart => contains_upper(pattern, self.case_folding),
+        }
+    }
+
+    fn is_case_sensitive_atoms<C: Copy + Into<char>>(&self, pattern: &[C]) -> bool {
+        match self.case {
+            CaseMatching::Respect => true,
+            CaseMatching::Ignore => false,
+            CaseMatching::Smart => contains_upper_atoms(pattern, self.case_folding),
+        }
+    }
+
+    fn fuzzy(
+        &self,
+        choice: &str,
+        pattern: &str,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        let case_sensitive = self.is_case_sensitive(pattern);
+        if choice.is_ascii() && pattern.is_ascii() {
+            return self.fuzzy_bytes(choice.as_bytes(), pattern.as_bytes(), with_pos);
+        }
+
+        if !may_match(
+            choice,
+            pattern,
+            case_sensitive,
+            self.case_folding,
+            self.normalize,
+        ) {
+            return None;
+        }
+
+        let result = {
+            let mut choice_chars = self
+                .c_cache
+                .get_or(|| RefCell::new(Vec::new()))
+                .borrow_mut();
+            let mut pattern_chars = self
+                .p_cache
+                .get_or(|| RefCell::new(Vec::new()))
+                .borrow_mut();
+
+            choice_chars.clear();
+            choice_chars.extend(choice.chars());
+            pattern_chars.clear();
+            pattern_chars.extend(pattern.chars());
+
+            self.fuzzy_transformed(
+                &mut choice_chars,
+                &mut pattern_chars,
+                case_sensitive,
+                with_pos,
+            )
+        };
+
+        self.release_caches();
+        result
+    }
+
+    /// Same as `fuzzy`, but on the characters decoded already
+    fn fuzzy_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        let case_sensitive = self.is_case_sensitive_atoms(pattern);
+        let result = if self.transforms(case_sensitive) {
+            self.fuzzy_copied(choice, pattern, case_sensitive, with_pos)
+        } else {
+            cheap_matches(choice, pattern, case_sensitive, self.case_folding).map(
+                |first_match_indices| {
+                    self.fuzzy_atoms(
+                        choice,
+                        pattern,
+                        &first_match_indices,
+                        case_sensitive,
+                        with_pos,
+                    )
+                },
+            )
+        };
+
+        self.release_caches();
+        result
+    }
+
+    /// Same as `fuzzy`, but every byte is a character
+    fn fuzzy_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        let case_sensitive = self.is_case_sensitive_atoms(pattern);
+        let result = if choice.is_ascii() && pattern.is_ascii() {
+            // ASCII characters are neither normalized nor expanded, match on the bytes directly
+            cheap_matches_ascii(choice, pattern, case_sensitive).map(|first_match_indices| {
+                self.fuzzy_atoms(
+                    choice,
+                    pattern,
+                    &first_match_indices,
+                    case_sensitive,
+                    with_pos,
+                )
+            })
+        } else if self.transforms(case_sensitive) {
+            let choice: Vec<char> = choice.iter().map(|&b| b.into()).collect();
+            let pattern: Vec<char> = pattern.iter().map(|&b| b.into()).collect();
+            self.fuzzy_copied(&choice, &pattern, case_sensitive, with_pos)
+        } else {
+            cheap_matches(choice, pattern, case_sensitive, self.case_folding).map(
+                |first_match_indices| {
+                    self.fuzzy_atoms(
+                        choice,
+                        pattern,
+                        &first_match_indices,
+                        case_sensitive,
+                        with_pos,
+                    )
+                },
+            )
+        };
+
+        self.release_caches();
+        result
+    }
+
+    /// Whether the characters would be transformed before matching, see `fuzzy_transformed`
+    fn transforms(&self, case_sensitive: bool) -> bool {
+        self.normalize || (!case_sensitive && self.case_folding == CaseFolding::Full)
+    }
+
+    /// Copy the characters into the caches to transform them, and match
+    fn fuzzy_copied(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let mut choice_chars = self
+            .c_cache
+            .get_or(|| RefCell::new(Vec::new()))
+            .borrow_mut();
+        let mut pattern_chars = self
+            .p_cache
+            .get_or(|| RefCell::new(Vec::new()))
+            .borrow_mut();
+
+        choice_chars.clear();
+        choice_chars.extend_from_slice(choice);
+        pattern_chars.clear();
+        pattern_chars.extend_from_slice(pattern);
+
+        self.fuzzy_transformed(
+            &mut choice_chars,
+            &mut pattern_chars,
+            case_sensitive,
+            with_pos,
+        )
+    }
+
+    /// Transform the characters in place and match them, the indices are mapped back to the
+    /// ones of the original choice.
+    fn fuzzy_transformed(
+        &self,
+        choice_chars: &mut Vec<char>,
+        pattern_chars: &mut Vec<char>,
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        // strip the diacritics if needed, and with full case folding, e.g. "ß" should match
+        // "ss", so expand these characters and match them one by one.
+        let expand_folding = !case_sensitive && self.case_folding == CaseFolding::Full;
+        transform_chars(pattern_chars, self.normalize, expand_folding);
+        let original_indices = transform_chars(choice_chars, self.normalize, expand_folding);
+
+        let mut result = cheap_matches(
+            choice_chars,
+            pattern_chars,
+            case_sensitive,
+            self.case_folding,
+        )
+        .map(|first_match_indices| {
+            self.fuzzy_atoms(
+                choice_chars,
+                pattern_chars,
+                &first_match_indices,
+                case_sensitive,
+                with_pos,
+            )
+        });
+        if let (Some((_, indices)), Some(original_indices)) =
+            (result.as_mut(), original_indices.as_ref())
+        {
+            map_original_indices(indices, original_indices);
+        }
+        result
+    }
+
+    fn release_caches(&self) {
+        if !self.use_cache {
+            // drop the allocated memory
+            self.c_cache.get().map(|cell| cell.replace(vec![]));
+            self.p_cache.get().map(|cell| cell.replace(vec![]));
+        }
+    }
+
+    fn fuzzy_atoms<C: Copy + Into<char>>(
+        &self,
+        choice: &[C],
+        pattern: &[C],
+        first_match_indices: &[usize],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> (ScoreType, Vec<IndexType>) {
+        fuzzy_match_v1(
+            choice,
+            pattern,
+            first_match_indices,
+            case_sensitive,
+            self.case_folding,
+            with_pos,
+        )
+    }
+}
+
+impl FuzzyMatcher for FzfV1Matcher {
+    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy(choice, pattern, true)
+    }
+
+    fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<ScoreType> {
+        self.fuzzy(choice, pattern, false).map(|(score, _)| score)
+    }
+
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        let expand_folding =
+            !self.is_case_sensitive(pattern) && self.case_folding == CaseFolding::Full;
+        let num_pattern = count_units(pattern, false, self.normalize, expand_folding);
+        Some(max_score(num_pattern))
+    }
+
+    fn narrows(&self) -> bool {
+        true
+    }
+
+    fn fuzzy_indices_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy_chars(choice, pattern, true)
+    }
+
+    fn fuzzy_match_chars(&self, choice: &[char], pattern: &[char]) -> Option<ScoreType> {
+        self.fuzzy_chars(choice, pattern, false)
+            .map(|(score, _)| score)
+    }
+
+    fn fuzzy_indices_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy_bytes(choice, pattern, true)
+    }
+
+    fn fuzzy_match_bytes(&self, choice: &[u8], pattern: &[u8]) -> Option<ScoreType> {
+        self.fuzzy_bytes(choice, pattern, false)
+            .map(|(score, _)| score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skim::SkimMatcherV2;
+    use crate::util::{assert_order, wrap_matches};
+
+    fn wrap_fuzzy_match(matcher: &dyn FuzzyMatcher, line: &str, pattern: &str) -> Option<String> {
+        let (_score, indices) = matcher.fuzzy_indices(line, pattern)?;
+        Some(wrap_matches(line, &indices))
+    }
+
+    /// (case sensitive, choice, pattern, start, end, score), where `start` and `end` are the
+    /// range of the matched indices. Recorded from fzf's algo_test.go, shared by v1 and v2.
+    const GOLDEN: &[(bool, &str, &str, usize, usize, i32)] = &[
+        (false, "fooBarbaz1", "oBZ", 2, 9, 16 * 3 + 7 - 3 - 3),
+        (
+            false,
+            "foo bar baz",
+            "fbb",
+            0,
+            9,
+            16 * 3 + 10 * 2 + 10 * 2 - 3 * 2 - 4,
+        ),
+        (
+            false,
+            "/AutomatorDocument.icns",
+            "rdoc",
+            9,
+            13,
+            16 * 4 + 7 + 4 * 2,
+        ),
+        (
+            false,
+            "/man1/zshcompctl.1",
+            "zshc",
+            6,
+            10,
+            16 * 4 + 9 * 2 + 9 * 3,
+        ),
+        (
+            false,
+            "/.oh-my-zsh/cache",
+            "zshc",
+            8,
+            13,
+            16 * 4 + 8 * 2 + 8 * 2 - 3 + 9,
+        ),
+        (false, "ab0123 456", "12356", 3, 10, 16 * 5 + 4 * 3 - 3 - 1),
+        (
+            false,
+            "abc123 456",
+            "12356",
+            3,
+            10,
+            16 * 5 + 7 * 2 + 7 * 2 + 4 - 3 - 1,
+        ),
+        (
+            false,
+            "foo/bar/baz",
+            "fbb",
+            0,
+            9,
+            16 * 3 + 10 * 2 + 9 * 2 - 3 * 2 - 4,
+        ),
+        (
+            false,
+            "fooBarBaz",
+            "fbb",
+            0,
+            7,
+            16 * 3 + 10 * 2 + 7 * 2 - 3 * 2 - 2,
+        ),
+        (
+            false,
+            "foo barbaz",
+            "fbb",
+            0,
+            8,
+            16 * 3 + 10 * 2 + 10 - 3 * 2 - 3,
+        ),
+        (false, "fooBar Baz", "foob", 0, 4, 16 * 4 + 10 * 2 + 10 * 3),
+        (
+            false,
+            "xFoo-Bar Baz",
+            "foo-b",
+            1,
+            6,
+            16 * 5 + 7 * 2 + 7 * 2 + 8 + 8,
+        ),
+        (true, "fooBarbaz", "oBz", 2, 9, 16 * 3 + 7 - 3 - 3),
+        (
+            true,
+            "Foo/Bar/Baz",
+            "FBB",
+            0,
+            9,
+            16 * 3 + 10 * 2 + 9 * 2 - 3 * 2 - 4,
+        ),
+        (
+            true,
+            "FooBarBaz",
+            "FBB",
+            0,
+            7,
+            16 * 3 + 10 * 2 + 7 * 2 - 3 * 2 - 2,
+        ),
+        (
+            true,
+            "FooBar Baz",
+            "FooB",
+            0,
+            4,
+            16 * 4 + 10 * 2 + 10 * 2 + 10,
+        ),
+        // consecutive bonus updated
+        (true, "foo-bar", "o-ba", 2, 6, 16 * 4 + 8 * 3),
+    ];
+
+    /// Recorded from fzf's algo_test.go, the choices don't match the patterns
+    const GOLDEN_NOT_MATCH: &[(bool, &str, &str)] = &[
+        (true, "fooBarbaz", "oBZ"),
+        (true, "Foo Bar Baz", "fbb"),
+        (true, "fooBarbaz", "fooBarbazz"),
+    ];
+
+    #[allow(clippy::unnecessary_cast)]
+    fn assert_golden(v1: bool) {
+        for &(case_sensitive, choice, pattern, start, end, score) in GOLDEN {
+            let matcher: Box<dyn FuzzyMatcher> = match (v1, case_sensitive) {
+                (true, true) => Box::new(FzfV1Matcher::default().respect_case()),
+                (true, false) => Box::new(FzfV1Matcher::default().ignore_case()),
+                (false, true) => Box::new(SkimMatcherV2::default().fzf_compat().respect_case()),
+                (false, false) => Box::new(SkimMatcherV2::default().fzf_compat().ignore_case()),
+            };
+            let (actual_score, indices) = matcher.fuzzy_indices(choice, pattern).unwrap();
+            assert_eq!(
+                (
+                    indices[0] as usize,
+                    indices[indices.len() - 1] as usize + 1,
+                    actual_score
+                ),
+                (start, end, score as ScoreType),
+                "{} / {}",
+                choice,
+                pattern
+            );
+            assert_eq!(matcher.fuzzy_match(choice, pattern), Some(actual_score));
+        }
+
+        for &(case_sensitive, choice, pattern) in GOLDEN_NOT_MATCH {
+            assert!(case_sensitive);
+            let matcher: Box<dyn FuzzyMatcher> = if v1 {
+                Box::new(FzfV1Matcher::default().respect_case())
+            } else {
+                Box::new(SkimMatcherV2::default().fzf_compat().respect_case())
+            };
+            assert_eq!(matcher.fuzzy_match(choice, pattern), None);
+        }
+    }
+
+    #[test]
+    fn test_golden_v1() {
+        assert_golden(true);
+    }
+
+    #[test]
+    fn test_golden_v2() {
+        assert_golden(false);
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_cast)]
+    fn test_v2() {
+        let matcher = SkimMatcherV2::default().fzf_compat().ignore_case();
+
+        // the best match is found, while v1 stops at the first one
+        let (score, indices) = matcher.fuzzy_indices("a___b_ab", "ab").unwrap();
+        assert_eq!(indices, [6, 7]);
+        assert_eq!(score, (16 * 2 + 8 * 2 + 8) as ScoreType);
+        let v1 = FzfV1Matcher::default();
+        assert_eq!(v1.fuzzy_indices("a___b_ab", "ab").unwrap().1, [0, 4]);
+
+        // on ties the earliest match wins
+        assert_eq!(matcher.fuzzy_indices("ab ab", "ab").unwrap().1, [0, 1]);
+        assert_eq!(matcher.fuzzy_indices("xa xa", "a").unwrap().1, [1]);
+
+        // normalized by default as in fzf, from fzf's algo_test.go
+        let matcher = SkimMatcherV2::default().fzf_compat();
+        assert_eq!(
+            matcher.fuzzy_indices("Só Danço Samba", "so"),
+            Some((62, vec![0, 1]))
+        );
+        assert_eq!(
+            matcher.fuzzy_indices("Só Danço Samba", "sodc"),
+            Some((97, vec![0, 1, 3, 6]))
+        );
+        assert_eq!(
+            matcher.fuzzy_indices("Danço", "danco"),
+            Some((140, vec![0, 1, 2, 3, 4]))
+        );
+        let matcher = matcher.normalize(false);
+        assert_eq!(matcher.fuzzy_match("Danço", "danco"), None);
+
+        // from fzf's algo_test.go
+        let mut long = vec![b'x'; u16::MAX as usize * 2];
+        long[u16::MAX as usize] = b'z';
+        let long = String::from_utf8(long).unwrap();
+        let matcher = SkimMatcherV2::default().fzf_compat().respect_case();
+        assert_eq!(
+            matcher.fuzzy_indices(&long, "zx"),
+            Some((
+                16 * 2 + 4,
+                vec![u16::MAX as IndexType, u16::MAX as IndexType + 1]
+            ))
+        );
+
+        // falls back to v1 when the matrix is too large
+        let matcher = SkimMatcherV2::default().fzf_compat().element_limit(4);
+        assert_eq!(matcher.fuzzy_indices("a___b_ab", "ab").unwrap().1, [0, 4]);
+        assert!(matcher.explain("a___b_ab", "ab").is_none());
+    }
+
+    #[test]
+    fn test_match_or_not() {
+        let matcher = FzfV1Matcher::default();
+        assert_eq!(Some(0), matcher.fuzzy_match("", ""));
+        assert_eq!(Some(0), matcher.fuzzy_match("abcdefaghi", ""));
+        assert_eq!(None, matcher.fuzzy_match("", "a"));
+        assert_eq!(None, matcher.fuzzy_match("abcdefaghi", "中"));
+        assert_eq!(None, matcher.fuzzy_match("abc", "abx"));
+        assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
+        assert!(matcher.fuzzy_match("Hello, 世界", "H世界").is_some());
+
+        // smart case
+        assert!(matcher.fuzzy_match("ABC", "abc").is_some());
+        assert!(matcher.fuzzy_match("abc", "ABC").is_none());
+        assert!(matcher.fuzzy_match("ÄBC", "äbc").is_some());
+
+        assert_eq!(
+            "[a]x[b]y[c]z",
+            &wrap_fuzzy_match(&matcher, "axbycz", "abc").unwrap()
+        );
+        assert_eq!(
+            "a_[a][b]",
+            &wrap_fuzzy_match(&matcher, "a_ab", "ab").unwrap()
+        );
+        assert_eq!(
+            "[H]ello, [世][界]",
+            &wrap_fuzzy_match(&matcher, "Hello, 世界", "H世界").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_match_quality() {
+        let matcher = FzfV1Matcher::default();
+        // boundary
+        assert_order(&matcher, "fb", &["foo_bar", "foobar"]);
+        // camel case
+        assert_order(&matcher, "fb", &["fooBar", "foobar"]);
+        // consecutive
+        assert_order(&matcher, "abc", &["abc", "a_bc", "axbycz"]);
+    }
+}
@@ -0,0 +1,343 @@
+//! Turn the matched indices into highlighted text.
+//!
+//! `segments` splits a choice into the runs of matched and unmatched characters, and the
+//! `Markup` implementations wrap the matched runs: `Ansi` for terminals, `Html` for web pages
+//! and `Brackets` for plain text. Implement `Markup` for other styles.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::highlight::{segments, Brackets, Html, Markup};
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let (_score, indices) = SkimMatcherV2::default().fuzzy_indices("src/main.rs", "smain").unwrap();
+//!
+//! let segments: Vec<(&str, bool)> = segments("src/main.rs", &indices).collect();
+//! assert_eq!(segments, [("s", true), ("rc/", false), ("main", true), (".rs", false)]);
+//!
+//! assert_eq!(Brackets::default().highlight("src/main.rs", &indices), "[s]rc/[main].rs");
+//! assert_eq!(
+//!     Html::default().highlight("src/main.rs", &indices),
+//!     "<mark>s</mark>rc/<mark>main</mark>.rs"
+//! );
+//! ```
+
+use std::ops::Range;
+
+use crate::render::{Span, SpanStyle};
+use crate::IndexType;
+
+/// Split the choice into `(text, is_match)` segments by the (sorted) char indices of the
+/// matched characters, the consecutive matched or unmatched characters are merged.
+#[allow(clippy::unnecessary_cast)]
+pub fn segments<'a>(choice: &'a str, indices: &[IndexType]) -> Segments<'a> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut peekable = indices.iter().peekable();
+    for (idx, (offset, ch)) in choice.char_indices().enumerate() {
+        if peekable.peek().is_none() {
+            break;
+        }
+        if peekable
+            .next_if(|&&next_idx| next_idx as usize == idx)
+            .is_none()
+        {
+            continue;
+        }
+
+        let end = offset + ch.len_utf8();
+        match ranges.last_mut() {
+            Some(range) if range.end == offset => range.end = end,
+            _ => ranges.push(offset..end),
+        }
+    }
+    Segments::new(choice, ranges)
+}
+
+/// Iterator of the `(text, is_match)` segments of a choice, see `segments`
+#[derive(Debug, Clone)]
+pub struct Segments<'a> {
+    choice: &'a str,
+    ranges: std::vec::IntoIter<Range<usize>>,
+    next_range: Option<Range<usize>>,
+    pos: usize,
+}
+
+impl<'a> Segments<'a> {
+    /// Split the choice by the sorted byte ranges of the matched text, e.g. the `ranges` of a
+    /// `MatchResult`, which works with grapheme clusters as well.
+    pub fn from_ranges(choice: &'a str, ranges: &[Range<usize>]) -> Self {
+        Self::new(choice, ranges.to_vec())
+    }
+
+    fn new(choice: &'a str, ranges: Vec<Range<usize>>) -> Self {
+        let mut ranges = ranges.into_iter();
+        let next_range = ranges.next();
+        Self {
+            choice,
+            ranges,
+            next_range,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = (&'a str, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(range) = self.next_range.clone() {
+            if self.pos < range.start {
+                let text = &self.choice[self.pos..range.start];
+                self.pos = range.start;
+                return Some((text, false));
+            }
+
+            self.next_range = self.ranges.next();
+            if range.start < range.end {
+                self.pos = range.end;
+                return Some((&self.choice[range], true));
+            }
+        }
+
+        if self.pos < self.choice.len() {
+            let text = &self.choice[self.pos..];
+            self.pos = self.choice.len();
+            Some((text, false))
+        } else {
+            None
+        }
+    }
+}
+
+/// How the matched text is marked up
+pub trait Markup {
+    /// Push the mark before a matched segment
+    fn open(&self, out: &mut String);
+
+    /// Push the mark after a matched segment
+    fn close(&self, out: &mut String);
+
+    /// Push the text of a segment, e.g. with the special characters escaped
+    fn escape(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Mark up the segments
+    fn markup<'a, I: IntoIterator<Item = (&'a str, bool)>>(&self, segments: I) -> String
+    where
+        Self: Sized,
+    {
+        let mut out = String::new();
+        for (text, matched) in segments {
+            if matched {
+                self.open(&mut out);
+                self.escape(text, &mut out);
+                self.close(&mut out);
+            } else {
+                self.escape(text, &mut out);
+            }
+        }
+        out
+    }
+
+    /// Mark up the choice by the char indices of the matched characters
+    fn highlight(&self, choice: &str, indices: &[IndexType]) -> String
+    where
+        Self: Sized,
+    {
+        self.markup(segments(choice, indices))
+    }
+
+    /// Mark up the spans rendered by `render::Renderer`, the ellipses are left as they are
+    fn markup_spans(&self, spans: &[Span]) -> String
+    where
+        Self: Sized,
+    {
+        self.markup(
+            spans
+                .iter()
+                .map(|span| (span.text.as_str(), span.style == SpanStyle::Matched)),
+        )
+    }
+}
+
+/// ANSI escape sequences for terminals, defaults to inverse video
+#[derive(Debug, Clone)]
+pub struct Ansi {
+    start: String,
+    end: String,
+}
+
+impl Default for Ansi {
+    fn default() -> Self {
+        Self::new("\x1b[7m", "\x1b[27m")
+    }
+}
+
+impl Ansi {
+    /// Wrap the matched text with the escape sequences, e.g. `"\x1b[1;31m"` and `"\x1b[0m"`
+    /// for bold red.
+    pub fn new(start: &str, end: &str) -> Self {
+        Self {
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+}
+
+impl Markup for Ansi {
+    fn open(&self, out: &mut String) {
+        out.push_str(&self.start);
+    }
+
+    fn close(&self, out: &mut String) {
+        out.push_str(&self.end);
+    }
+}
+
+/// HTML, the matched text is wrapped in `<mark>` by default and all the text is escaped
+#[derive(Debug, Clone)]
+pub struct Html {
+    tag: String,
+}
+
+impl Default for Html {
+    fn default() -> Self {
+        Self::new("mark")
+    }
+}
+
+impl Html {
+    /// Wrap the matched text in the element of the tag, e.g. "b" or "em"
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+        }
+    }
+}
+
+impl Markup for Html {
+    fn open(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.tag);
+        out.push('>');
+    }
+
+    fn close(&self, out: &mut String) {
+        out.push_str("</");
+        out.push_str(&self.tag);
+        out.push('>');
+    }
+
+    fn escape(&self, text: &str, out: &mut String) {
+        for ch in text.chars() {
+            match ch {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#39;"),
+                _ => out.push(ch),
+            }
+        }
+    }
+}
+
+/// Plain text, the matched text is wrapped in `[` and `]` by default
+#[derive(Debug, Clone)]
+pub struct Brackets {
+    open: String,
+    close: String,
+}
+
+impl Default for Brackets {
+    fn default() -> Self {
+        Self::new("[", "]")
+    }
+}
+
+impl Brackets {
+    pub fn new(open: &str, close: &str) -> Self {
+        Self {
+            open: open.to_string(),
+            close: close.to_string(),
+        }
+    }
+}
+
+impl Markup for Brackets {
+    fn open(&self, out: &mut String) {
+        out.push_str(&self.open);
+    }
+
+    fn close(&self, out: &mut String) {
+        out.push_str(&self.close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Renderer;
+    use crate::MatchResult;
+
+    #[test]
+    fn test_segments() {
+        let collect = |choice, indices: &[IndexType]| -> Vec<(&str, bool)> {
+            segments(choice, indices).collect()
+        };
+        assert_eq!(collect("abc", &[]), [("abc", false)]);
+        assert_eq!(collect("abc", &[0, 1, 2]), [("abc", true)]);
+        assert_eq!(
+            collect("abc", &[1]),
+            [("a", false), ("b", true), ("c", false)]
+        );
+        assert_eq!(collect("", &[]), []);
+        assert_eq!(
+            collect("Hello, 世界", &[0, 7, 8]),
+            [("H", true), ("ello, ", false), ("世界", true)]
+        );
+
+        let result = MatchResult::from_graphemes("cafe\u{301} bar", 0, vec![3, 5]);
+        let segments: Vec<(&str, bool)> =
+            Segments::from_ranges("cafe\u{301} bar", &result.ranges).collect();
+        assert_eq!(
+            segments,
+            [
+                ("caf", false),
+                ("e\u{301}", true),
+                (" ", false),
+                ("b", true),
+                ("ar", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markup() {
+        assert_eq!(
+            Brackets::default().highlight("abcd", &[0, 2, 3]),
+            "[a]b[cd]"
+        );
+        assert_eq!(
+            Brackets::new("<", ">").highlight("abcd", &[0, 2, 3]),
+            "<a>b<cd>"
+        );
+        assert_eq!(Ansi::default().highlight("abc", &[1]), "a\x1b[7mb\x1b[27mc");
+        assert_eq!(
+            Ansi::new("\x1b[31m", "\x1b[0m").highlight("abc", &[0]),
+            "\x1b[31ma\x1b[0mbc"
+        );
+        assert_eq!(
+            Html::default().highlight("<a&b>", &[1, 3]),
+            "&lt;<mark>a</mark>&amp;<mark>b</mark>&gt;"
+        );
+        assert_eq!(Html::new("b").highlight("ab", &[0]), "<b>a</b>b");
+    }
+
+    #[test]
+    fn test_markup_spans() {
+        let spans = Renderer::default().render("0123456789abcdefghij", &[10, 11], 10);
+        assert_eq!(Brackets::default().markup_spans(&spans), "..89[ab]cd..");
+    }
+}
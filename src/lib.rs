@@ -1,7 +1,21 @@
+pub mod batch;
 pub mod clangd;
+pub mod classifier;
+pub mod explain;
+pub mod extended;
+pub mod field;
+pub mod fzf;
+pub mod highlight;
+pub mod path;
+pub mod rank;
+pub mod render;
+pub mod session;
 pub mod skim;
 mod util;
 
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
 #[cfg(not(feature = "compact"))]
 type IndexType = usize;
 #[cfg(not(feature = "compact"))]
@@ -12,6 +26,100 @@ type IndexType = u32;
 #[cfg(feature = "compact")]
 type ScoreType = i32;
 
+/// How characters are compared when matching case insensitively
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+pub enum CaseFolding {
+    /// Only ASCII letters are folded, e.g. "ä" won't match "Ä"
+    #[default]
+    Ascii,
+    /// Unicode simple case folding, one character folds to exactly one character,
+    /// e.g. "ä" matches "Ä", "σ" matches "ς"
+    Simple,
+    /// Unicode full case folding, a character may fold to multiple characters,
+    /// e.g. "ss" matches "ß", "fi" matches "ﬁ"
+    Full,
+}
+
+/// The detailed result of a match, so that UIs could slice the choice directly instead of
+/// walking through the characters again.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct MatchResult {
+    pub score: ScoreType,
+    /// indices of the matched characters, same as the ones returned by `fuzzy_indices`
+    pub indices: Vec<IndexType>,
+    /// byte offsets of the matched characters in the choice
+    pub byte_offsets: Vec<usize>,
+    /// byte ranges of the contiguous runs of matched characters, e.g. the matched runs of
+    /// "src/main.rs" on "smain" are `[0..1, 4..8]`
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl MatchResult {
+    /// Build the result from the score and the (sorted) char indices of the matched characters
+    pub fn new(choice: &str, score: ScoreType, indices: Vec<IndexType>) -> Self {
+        let units = choice
+            .char_indices()
+            .map(|(offset, ch)| offset..offset + ch.len_utf8());
+        Self::from_units(units, score, indices)
+    }
+
+    /// Same as `new`, but the indices are the ones of the extended grapheme clusters, e.g. the
+    /// ones returned by the matchers with `graphemes(true)`, and the byte ranges cover the
+    /// whole clusters.
+    pub fn from_graphemes(choice: &str, score: ScoreType, indices: Vec<IndexType>) -> Self {
+        let units = choice
+            .grapheme_indices(true)
+            .map(|(offset, grapheme)| offset..offset + grapheme.len());
+        Self::from_units(units, score, indices)
+    }
+
+    /// Build the result from the byte ranges of the units the indices refer to
+    #[allow(clippy::unnecessary_cast)]
+    fn from_units(
+        units: impl Iterator<Item = Range<usize>>,
+        score: ScoreType,
+        indices: Vec<IndexType>,
+    ) -> Self {
+        let mut byte_offsets = Vec::with_capacity(indices.len());
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+
+        let mut peekable = indices.iter().peekable();
+        for (idx, unit) in units.enumerate() {
+            match peekable.peek() {
+                Some(&&next_idx) if next_idx as usize == idx => {
+                    let _ = peekable.next();
+                }
+                Some(_) => continue,
+                None => break,
+            }
+
+            byte_offsets.push(unit.start);
+            match ranges.last_mut() {
+                Some(range) if range.end == unit.start => range.end = unit.end,
+                _ => ranges.push(unit),
+            }
+        }
+
+        Self {
+            score,
+            indices,
+            byte_offsets,
+            ranges,
+        }
+    }
+}
+
+/// Normalize the score into `[0, 1]` by the best score of its pattern, see
+/// `FuzzyMatcher::fuzzy_match_normalized`. Without a positive best score every match is 1.0.
+/// Normalizing the matches of a pattern this way needs `max_score` only once.
+#[allow(clippy::unnecessary_cast)]
+pub fn normalize_score(score: ScoreType, max_score: Option<ScoreType>) -> f64 {
+    match max_score {
+        Some(max_score) if max_score > 0 => (score as f64 / max_score as f64).clamp(0.0, 1.0),
+        _ => 1.0,
+    }
+}
+
 pub trait FuzzyMatcher: Send + Sync {
     /// fuzzy match choice with pattern, and return the score & matched indices of characters
     fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)>;
@@ -20,4 +128,367 @@ pub trait FuzzyMatcher: Send + Sync {
     fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<ScoreType> {
         self.fuzzy_indices(choice, pattern).map(|(score, _)| score)
     }
+
+    /// fuzzy match choice with pattern, and return the score, the matched indices as well as
+    /// the byte offsets and ranges of them
+    fn fuzzy_result(&self, choice: &str, pattern: &str) -> Option<MatchResult> {
+        let (score, indices) = self.fuzzy_indices(choice, pattern)?;
+        if self.indexes_graphemes() {
+            Some(MatchResult::from_graphemes(choice, score, indices))
+        } else {
+            Some(MatchResult::new(choice, score, indices))
+        }
+    }
+
+    /// Whether the indices returned by the `&str` methods are the ones of extended grapheme
+    /// clusters instead of characters, e.g. the matchers with `graphemes(true)`.
+    fn indexes_graphemes(&self) -> bool {
+        false
+    }
+
+    /// Whether a choice that doesn't match a pattern never matches the patterns extended from
+    /// it, e.g. "ab" from "a", so that `session::MatchSession` could only rescore the matches
+    /// of the last query when it is extended. Defaults to `false`, i.e. no such guarantee.
+    fn narrows(&self) -> bool {
+        false
+    }
+
+    /// Same as `fuzzy_indices`, but on the characters decoded already, so that callers caching
+    /// the decoded choices skip the decoding. The default implementation collects them into
+    /// strings, matchers should override it to match the characters directly.
+    fn fuzzy_indices_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let choice: String = choice.iter().collect();
+        let pattern: String = pattern.iter().collect();
+        self.fuzzy_indices(&choice, &pattern)
+    }
+
+    /// Same as `fuzzy_match`, but on the characters decoded already
+    fn fuzzy_match_chars(&self, choice: &[char], pattern: &[char]) -> Option<ScoreType> {
+        self.fuzzy_indices_chars(choice, pattern)
+            .map(|(score, _)| score)
+    }
+
+    /// Same as `fuzzy_indices`, but every byte is a character, e.g. ASCII text. The bytes that
+    /// are not ASCII are treated as the characters of the same code points, i.e. Latin-1.
+    fn fuzzy_indices_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let choice: String = choice.iter().map(|&b| char::from(b)).collect();
+        let pattern: String = pattern.iter().map(|&b| char::from(b)).collect();
+        self.fuzzy_indices(&choice, &pattern)
+    }
+
+    /// Same as `fuzzy_match`, but every byte is a character
+    fn fuzzy_match_bytes(&self, choice: &[u8], pattern: &[u8]) -> Option<ScoreType> {
+        self.fuzzy_indices_bytes(choice, pattern)
+            .map(|(score, _)| score)
+    }
+
+    /// The upper bound of the scores of the pattern on any choice under the current
+    /// configuration, None if there's no such bound. The matchers of this crate derive it from
+    /// their score configs, while the default is the score of a choice identical to the
+    /// pattern, which custom matchers should override if a choice could score higher.
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        self.fuzzy_match(pattern, pattern)
+    }
+
+    /// fuzzy match choice with pattern, and return the score normalized into `[0, 1]` by
+    /// `max_score`, so that the scores of different matchers and patterns are comparable.
+    fn fuzzy_match_normalized(&self, choice: &str, pattern: &str) -> Option<f64> {
+        let score = self.fuzzy_match(choice, pattern)?;
+        Some(normalize_score(score, self.max_score(pattern)))
+    }
+}
+
+/// Boxed matchers, e.g. the ones picked at runtime, could be wrapped by the other matchers
+impl<M: FuzzyMatcher + ?Sized> FuzzyMatcher for Box<M> {
+    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+        (**self).fuzzy_indices(choice, pattern)
+    }
+
+    fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<ScoreType> {
+        (**self).fuzzy_match(choice, pattern)
+    }
+
+    fn fuzzy_result(&self, choice: &str, pattern: &str) -> Option<MatchResult> {
+        (**self).fuzzy_result(choice, pattern)
+    }
+
+    fn fuzzy_indices_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        (**self).fuzzy_indices_chars(choice, pattern)
+    }
+
+    fn fuzzy_match_chars(&self, choice: &[char], pattern: &[char]) -> Option<ScoreType> {
+        (**self).fuzzy_match_chars(choice, pattern)
+    }
+
+    fn fuzzy_indices_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        (**self).fuzzy_indices_bytes(choice, pattern)
+    }
+
+    fn fuzzy_match_bytes(&self, choice: &[u8], pattern: &[u8]) -> Option<ScoreType> {
+        (**self).fuzzy_match_bytes(choice, pattern)
+    }
+
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        (**self).max_score(pattern)
+    }
+
+    fn fuzzy_match_normalized(&self, choice: &str, pattern: &str) -> Option<f64> {
+        (**self).fuzzy_match_normalized(choice, pattern)
+    }
+
+    fn indexes_graphemes(&self) -> bool {
+        (**self).indexes_graphemes()
+    }
+
+    fn narrows(&self) -> bool {
+        (**self).narrows()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clangd::ClangdMatcher;
+    use crate::extended::ExtendedMatcher;
+    use crate::fzf::FzfV1Matcher;
+    use crate::path::PathMatcher;
+    use crate::skim::SkimMatcherV2;
+
+    #[test]
+    fn test_match_result() {
+        let result = MatchResult::new("src/main.rs", 10, vec![0, 4, 5, 6, 7]);
+        assert_eq!(result.byte_offsets, vec![0, 4, 5, 6, 7]);
+        assert_eq!(result.ranges, vec![0..1, 4..8]);
+
+        let result = MatchResult::new("Hello, 世界!", 10, vec![0, 1, 7, 8]);
+        assert_eq!(result.byte_offsets, vec![0, 1, 7, 10]);
+        assert_eq!(result.ranges, vec![0..2, 7..13]);
+        assert_eq!(&"Hello, 世界!"[result.ranges[1].clone()], "世界");
+
+        let result = MatchResult::new("abc", 0, vec![]);
+        assert!(result.byte_offsets.is_empty());
+        assert!(result.ranges.is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_result() {
+        let matcher = SkimMatcherV2::default();
+        let result = matcher.fuzzy_result("Hello, 世界", "H世界").unwrap();
+        let (score, indices) = matcher.fuzzy_indices("Hello, 世界", "H世界").unwrap();
+        assert_eq!(result.score, score);
+        assert_eq!(result.indices, indices);
+        assert_eq!(result.ranges, vec![0..1, 7..13]);
+        assert!(matcher.fuzzy_result("abc", "abx").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_match_normalized() {
+        let matchers: Vec<Box<dyn FuzzyMatcher>> = vec![
+            Box::new(SkimMatcherV2::default()),
+            Box::new(ClangdMatcher::default()),
+        ];
+        for matcher in matchers.iter() {
+            assert_eq!(matcher.fuzzy_match_normalized("abc", ""), Some(1.0));
+            assert!(matcher.fuzzy_match_normalized("abc", "abx").is_none());
+
+            let identical = matcher.fuzzy_match_normalized("abc", "abc").unwrap();
+            let prefix = matcher.fuzzy_match_normalized("abcdef", "abc").unwrap();
+            let scattered = matcher.fuzzy_match_normalized("axbxcxdef", "abc").unwrap();
+            assert!(identical <= 1.0);
+            assert!(prefix <= identical);
+            assert!(scattered < prefix);
+            assert!(scattered >= 0.0);
+        }
+
+        // the score of "a.." is higher than the one of ".." itself
+        let matcher = SkimMatcherV2::default().fzf_compat();
+        assert!(matcher.fuzzy_match_normalized("a..", "..").unwrap() < 1.0);
+        // a poor match of clangd scores below 0
+        let matcher = ClangdMatcher::default();
+        assert!(matcher.fuzzy_match("a..", "..").unwrap() < 0);
+        assert_eq!(matcher.fuzzy_match_normalized("a..", ".."), Some(0.0));
+    }
+
+    #[test]
+    fn test_max_score() {
+        let matchers: Vec<Box<dyn FuzzyMatcher>> = vec![
+            Box::new(SkimMatcherV2::default()),
+            Box::new(SkimMatcherV2::default().element_limit(1)),
+            Box::new(SkimMatcherV2::default().max_typos(1)),
+            Box::new(SkimMatcherV2::default().max_typos(1).typo_penalty(-8)),
+            Box::new(SkimMatcherV2::default().case_folding(CaseFolding::Full)),
+            Box::new(SkimMatcherV2::default().normalize(true).graphemes(true)),
+            Box::new(SkimMatcherV2::default().fzf_compat()),
+            Box::new(SkimMatcherV2::default().fzf_compat().element_limit(1)),
+            Box::new(ClangdMatcher::default()),
+            Box::new(ClangdMatcher::default().case_folding(CaseFolding::Full)),
+            Box::new(FzfV1Matcher::default()),
+            Box::new(ExtendedMatcher::new(SkimMatcherV2::default())),
+            Box::new(
+                ExtendedMatcher::new(ClangdMatcher::default()).case_folding(CaseFolding::Full),
+            ),
+            Box::new(PathMatcher::new(SkimMatcherV2::default())),
+            Box::new(PathMatcher::new(FzfV1Matcher::default()).segment_penalty(-4)),
+        ];
+        let patterns = [
+            "a",
+            "ab",
+            "aB",
+            "ba",
+            "..",
+            "a.",
+            "a b",
+            "a/b",
+            "/b",
+            "ss",
+            "ß",
+            "'ab",
+            "^a b$ | .",
+        ];
+
+        // every choice of up to 4 characters of the alphabet, and some longer ones
+        let alphabet = ['a', 'b', 'A', 'B', '.', ' ', '/', 's', 'ß'];
+        let mut choices = vec![String::new()];
+        let mut last = choices.clone();
+        for _ in 0..4 {
+            last = last
+                .iter()
+                .flat_map(|choice| alphabet.iter().map(move |&ch| format!("{}{}", choice, ch)))
+                .collect();
+            choices.extend(last.iter().cloned());
+        }
+        choices.extend(
+            [
+                "a..",
+                "a b/a b",
+                "ab ab.ab",
+                "x/a/b.ab",
+                "Straße",
+                "é a\u{301}b",
+            ]
+            .iter()
+            .map(|choice| choice.to_string()),
+        );
+
+        for (idx, matcher) in matchers.iter().enumerate() {
+            for pattern in patterns.iter() {
+                let max_score = matcher.max_score(pattern).unwrap();
+                for choice in choices.iter() {
+                    if let Some(score) = matcher.fuzzy_match(choice, pattern) {
+                        assert!(
+                            score <= max_score,
+                            "matcher #{}: {:?} on {:?} scores {} > {}",
+                            idx,
+                            pattern,
+                            choice,
+                            score,
+                            max_score
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_chars_and_bytes() {
+        let matchers: Vec<Box<dyn FuzzyMatcher>> = vec![
+            Box::new(SkimMatcherV2::default()),
+            Box::new(SkimMatcherV2::default().max_typos(1)),
+            Box::new(SkimMatcherV2::default().normalize(true)),
+            Box::new(SkimMatcherV2::default().case_folding(CaseFolding::Full)),
+            Box::new(SkimMatcherV2::default().fzf_compat()),
+            Box::new(ClangdMatcher::default().smart_case()),
+            Box::new(ClangdMatcher::default().normalize(true)),
+            Box::new(FzfV1Matcher::default()),
+            Box::new(FzfV1Matcher::default().case_folding(CaseFolding::Full)),
+            // the default implementations
+            Box::new(ExtendedMatcher::new(SkimMatcherV2::default())),
+        ];
+        let cases = [
+            ("axbycz", "abc"),
+            ("FooBar", "fb"),
+            ("FooBar", "Fb"),
+            ("abc", "abx"),
+            ("abc", "acb"),
+            ("abc", ""),
+            ("Straße", "strasse"),
+            ("résumé", "resume"),
+            ("Só Danço Samba", "sds"),
+            ("Hello, 世界", "H世界"),
+        ];
+        for (idx, matcher) in matchers.iter().enumerate() {
+            for &(choice, pattern) in cases.iter() {
+                let expected = matcher.fuzzy_indices(choice, pattern);
+                let choice_chars: Vec<char> = choice.chars().collect();
+                let pattern_chars: Vec<char> = pattern.chars().collect();
+                assert_eq!(
+                    matcher.fuzzy_indices_chars(&choice_chars, &pattern_chars),
+                    expected,
+                    "matcher {}: {} {}",
+                    idx,
+                    choice,
+                    pattern
+                );
+                assert_eq!(
+                    matcher.fuzzy_match_chars(&choice_chars, &pattern_chars),
+                    expected.as_ref().map(|(score, _)| *score)
+                );
+
+                // every byte is a character, so only Latin-1 text is the same as `&str`
+                if choice_chars
+                    .iter()
+                    .chain(pattern_chars.iter())
+                    .all(|&ch| (ch as u32) < 256)
+                {
+                    let choice_bytes: Vec<u8> = choice_chars.iter().map(|&ch| ch as u8).collect();
+                    let pattern_bytes: Vec<u8> = pattern_chars.iter().map(|&ch| ch as u8).collect();
+                    assert_eq!(
+                        matcher.fuzzy_indices_bytes(&choice_bytes, &pattern_bytes),
+                        expected,
+                        "matcher {}: {} {}",
+                        idx,
+                        choice,
+                        pattern
+                    );
+                    assert_eq!(
+                        matcher.fuzzy_match_bytes(&choice_bytes, &pattern_bytes),
+                        expected.as_ref().map(|(score, _)| *score)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_boxed() {
+        let boxed: Box<dyn FuzzyMatcher> = Box::new(SkimMatcherV2::default().graphemes(true));
+        let matcher = ExtendedMatcher::new(boxed);
+        let result = matcher.fuzzy_result("cafe\u{301} bar", "eb").unwrap();
+        assert_eq!(result.indices, [3, 5]);
+
+        let boxed: Box<dyn FuzzyMatcher> = Box::new(SkimMatcherV2::default().graphemes(true));
+        let result = boxed.fuzzy_result("cafe\u{301} bar", "eb").unwrap();
+        assert_eq!(result.ranges, [3..6, 7..8]);
+        assert_eq!(
+            boxed.max_score("ab"),
+            SkimMatcherV2::default().max_score("ab")
+        );
+    }
 }
@@ -0,0 +1,405 @@
+//! Match file paths segment by segment, on top of any `FuzzyMatcher`.
+//!
+//! The inner matchers already give bonuses after the separators, e.g. `/` and `\` are hard
+//! separators of `SkimClassifier`, but they have no idea of which segment is the file name.
+//! `PathMatcher` prefers the matches in the last component (the basename) and the matches
+//! spanning fewer segments. A pattern containing separators anchors its pieces to segments,
+//! e.g. "src/main" matches "src" in a segment and "main" in a later one.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::path::PathMatcher;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = PathMatcher::new(SkimMatcherV2::default());
+//! let in_basename = matcher.fuzzy_match("src/foo/bar.rs", "bar").unwrap();
+//! let in_directory = matcher.fuzzy_match("src/bar/foo.rs", "bar").unwrap();
+//! assert!(in_basename > in_directory);
+//!
+//! let (_score, indices) = matcher.fuzzy_indices("src/bin/main.rs", "s/m").unwrap();
+//! assert_eq!(indices, [0, 8]);
+//! assert!(matcher.fuzzy_match("src/main.rs", "main/src").is_none());
+//! ```
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::util::max_matched_chars;
+use crate::{FuzzyMatcher, IndexType, ScoreType};
+
+const DEFAULT_BASENAME_BONUS: ScoreType = 8;
+const DEFAULT_SEGMENT_PENALTY: ScoreType = 16;
+
+fn is_separator(ch: char) -> bool {
+    ch == '/' || ch == '\\'
+}
+
+/// A segment of a path, i.e. the text between separators
+#[derive(Debug)]
+struct Segment<'a> {
+    text: &'a str,
+    /// index of the first unit in the path, a character or a grapheme cluster
+    start: usize,
+    /// index after the last unit in the path
+    end: usize,
+}
+
+/// Split the path into segments, the empty ones (e.g. the one before a leading `/`) are kept so
+/// that the indices stay simple. With `graphemes` the indices are the ones of the grapheme
+/// clusters, and a cluster starting with a separator is one.
+fn split_segments(path: &str, graphemes: bool) -> Vec<Segment<'_>> {
+    let units: Vec<(usize, &str)> = if graphemes {
+        path.grapheme_indices(true).collect()
+    } else {
+        path.char_indices()
+            .map(|(offset, ch)| (offset, &path[offset..offset + ch.len_utf8()]))
+            .collect()
+    };
+
+    let mut segments = Vec::new();
+    let mut byte_start = 0;
+    let mut start = 0;
+    for (idx, &(offset, unit)) in units.iter().enumerate() {
+        if unit.starts_with(is_separator) {
+            segments.push(Segment {
+                text: &path[byte_start..offset],
+                start,
+                end: idx,
+            });
+            byte_start = offset + unit.len();
+            start = idx + 1;
+        }
+    }
+    segments.push(Segment {
+        text: &path[byte_start..],
+        start,
+        end: units.len(),
+    });
+    segments
+}
+
+/// The index of the basename, i.e. the last non-empty segment, so that the basename of a
+/// directory with trailing separator is its own name.
+fn basename_of(segments: &[Segment]) -> usize {
+    segments
+        .iter()
+        .rposition(|segment| !segment.text.is_empty())
+        .unwrap_or(segments.len() - 1)
+}
+
+/// Wraps a `FuzzyMatcher` to match file paths, the segments are separated by `/` or `\`.
+///
+/// Without separators in the pattern, it is matched against both the whole path and the
+/// basename alone, and the better one is taken. With separators, every non-empty piece of the
+/// pattern is matched inside a single segment, and the pieces must appear in order in
+/// different segments.
+///
+/// The score of the inner matcher is then weighted: every matched character in the basename
+/// gets `basename_bonus`, and every segment touched besides the first one costs
+/// `segment_penalty`.
+#[derive(Debug)]
+pub struct PathMatcher<M: FuzzyMatcher> {
+    matcher: M,
+    basename_bonus: ScoreType,
+    segment_penalty: ScoreType,
+}
+
+impl<M: FuzzyMatcher> PathMatcher<M> {
+    pub fn new(matcher: M) -> Self {
+        Self {
+            matcher,
+            basename_bonus: DEFAULT_BASENAME_BONUS,
+            segment_penalty: DEFAULT_SEGMENT_PENALTY,
+        }
+    }
+
+    /// The bonus of every matched character in the basename, defaults to 8.
+    pub fn basename_bonus(mut self, basename_bonus: ScoreType) -> Self {
+        self.basename_bonus = basename_bonus;
+        self
+    }
+
+    /// The penalty of every touched segment besides the first one, defaults to 16.
+    pub fn segment_penalty(mut self, segment_penalty: ScoreType) -> Self {
+        self.segment_penalty = segment_penalty;
+        self
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.matcher
+    }
+
+    /// Match the pattern inside a segment, the indices are the ones in the path
+    #[allow(clippy::unnecessary_cast)]
+    fn segment_indices(
+        &self,
+        segment: &Segment,
+        pattern: &str,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let (score, mut indices) = self.matcher.fuzzy_indices(segment.text, pattern)?;
+        for idx in indices.iter_mut() {
+            *idx += segment.start as IndexType;
+        }
+        Some((score, indices))
+    }
+
+    /// Weigh the score of the inner matcher by where the matched characters are
+    #[allow(clippy::unnecessary_cast)]
+    fn weigh(&self, segments: &[Segment], score: ScoreType, indices: &[IndexType]) -> ScoreType {
+        let basename = basename_of(segments);
+        let mut in_basename = 0;
+        let mut touched = 0;
+        let mut last_touched = None;
+
+        // the indices are sorted, so walk through the segments along with them
+        let mut seg_idx = 0;
+        for &idx in indices {
+            let idx = idx as usize;
+            while seg_idx + 1 < segments.len() && idx >= segments[seg_idx].end {
+                seg_idx += 1;
+            }
+            if last_touched != Some(seg_idx) {
+                touched += 1;
+                last_touched = Some(seg_idx);
+            }
+            if seg_idx == basename {
+                in_basename += 1;
+            }
+        }
+
+        score + in_basename * self.basename_bonus
+            - (touched - 1).max(0) as ScoreType * self.segment_penalty
+    }
+
+    fn unanchored_indices(
+        &self,
+        path: &str,
+        segments: &[Segment],
+        pattern: &str,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        let whole = self.matcher.fuzzy_indices(path, pattern);
+        let basename = self.segment_indices(&segments[basename_of(segments)], pattern);
+
+        whole
+            .into_iter()
+            .chain(basename)
+            .map(|(score, indices)| (self.weigh(segments, score, &indices), indices))
+            .max_by_key(|(score, _)| *score)
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    fn anchored_indices(
+        &self,
+        segments: &[Segment],
+        pieces: &[&str],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        // best[j]: the best match of the pieces so far, with the last piece in segment j. The
+        // basename bonus is added piece by piece, while the segment penalty is the same for all
+        // the matches as every piece touches exactly one segment.
+        let basename = basename_of(segments);
+        let empty = (0, Vec::new());
+        let mut best: Vec<Option<(ScoreType, Vec<IndexType>)>> = vec![None; segments.len()];
+        for (piece_idx, &piece) in pieces.iter().enumerate() {
+            let mut next = vec![None; segments.len()];
+            let mut best_before: Option<&(ScoreType, Vec<IndexType>)> = None;
+            for (seg_idx, segment) in segments.iter().enumerate() {
+                let prefix = if piece_idx == 0 {
+                    Some(&empty)
+                } else {
+                    best_before
+                };
+
+                if let Some((prefix_score, prefix_indices)) = prefix {
+                    if let Some((mut score, indices)) = self.segment_indices(segment, piece) {
+                        if seg_idx == basename {
+                            score += indices.len() as ScoreType * self.basename_bonus;
+                        }
+                        let mut all_indices = prefix_indices.clone();
+                        all_indices.extend(indices);
+                        next[seg_idx] = Some((prefix_score + score, all_indices));
+                    }
+                }
+
+                if let Some(current) = best[seg_idx].as_ref() {
+                    let better = match best_before {
+                        Some(before) => current.0 > before.0,
+                        None => true,
+                    };
+                    if better {
+                        best_before = Some(current);
+                    }
+                }
+            }
+            best = next;
+        }
+
+        let penalty = (pieces.len() - 1) as ScoreType * self.segment_penalty;
+        best.into_iter()
+            .flatten()
+            .max_by_key(|(score, _)| *score)
+            .map(|(score, indices)| (score - penalty, indices))
+    }
+}
+
+impl<M: FuzzyMatcher> FuzzyMatcher for PathMatcher<M> {
+    fn fuzzy_indices(&self, choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
+        if pattern.is_empty() {
+            return self.matcher.fuzzy_indices(choice, pattern);
+        }
+
+        let segments = split_segments(choice, self.matcher.indexes_graphemes());
+        let pieces: Vec<&str> = pattern
+            .split(is_separator)
+            .filter(|piece| !piece.is_empty())
+            .collect();
+
+        if !pattern.contains(is_separator) {
+            self.unanchored_indices(choice, &segments, pattern)
+        } else if pieces.is_empty() {
+            // nothing but separators
+            self.matcher.fuzzy_indices(choice, pattern)
+        } else {
+            self.anchored_indices(&segments, &pieces)
+        }
+    }
+
+    /// The best score of the inner matcher, with every matched character in the basename and
+    /// the penalty of the touched segments if it is a reward. A piece of an anchored pattern
+    /// is matched in its own segment, so at most one of them is in the basename.
+    #[allow(clippy::unnecessary_cast)]
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        let pieces: Vec<&str> = pattern
+            .split(is_separator)
+            .filter(|piece| !piece.is_empty())
+            .collect();
+        let basename_bonus =
+            |piece: &str| max_matched_chars(piece) as ScoreType * self.basename_bonus.max(0);
+
+        if !pattern.contains(is_separator) {
+            let extra_segments = max_matched_chars(pattern).saturating_sub(1) as ScoreType;
+            Some(
+                self.matcher.max_score(pattern)?
+                    + basename_bonus(pattern)
+                    + extra_segments * (-self.segment_penalty).max(0),
+            )
+        } else if pieces.is_empty() {
+            self.matcher.max_score(pattern)
+        } else {
+            let mut max_score = 0;
+            for &piece in pieces.iter() {
+                max_score += self.matcher.max_score(piece)?;
+            }
+            let max_bonus = pieces.iter().map(|&piece| basename_bonus(piece)).max();
+            let penalty = (pieces.len() - 1) as ScoreType * self.segment_penalty;
+            Some(max_score + max_bonus.unwrap_or(0) - penalty)
+        }
+    }
+
+    fn indexes_graphemes(&self) -> bool {
+        self.matcher.indexes_graphemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skim::SkimMatcherV2;
+    use crate::util::{assert_order, wrap_matches};
+
+    fn wrap_fuzzy_match(matcher: &dyn FuzzyMatcher, line: &str, pattern: &str) -> Option<String> {
+        let (_score, indices) = matcher.fuzzy_indices(line, pattern)?;
+        Some(wrap_matches(line, &indices))
+    }
+
+    #[test]
+    fn test_split_segments() {
+        let segments = split_segments("/usr\\文档/a.rs/", false);
+        let texts: Vec<&str> = segments.iter().map(|segment| segment.text).collect();
+        assert_eq!(texts, ["", "usr", "文档", "a.rs", ""]);
+        assert_eq!((segments[2].start, segments[2].end), (5, 7));
+        assert_eq!(basename_of(&segments), 3);
+
+        let segments = split_segments("e\u{301}/cafe\u{301}", true);
+        assert_eq!((segments[1].start, segments[1].end), (2, 6));
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let matcher = PathMatcher::new(SkimMatcherV2::default().graphemes(true));
+        assert!(matcher.indexes_graphemes());
+        let (_, indices) = matcher.fuzzy_indices("x/cafe\u{301} b", "eb").unwrap();
+        assert_eq!(indices, [5, 7]);
+        let result = matcher.fuzzy_result("x/cafe\u{301} b", "eb").unwrap();
+        assert_eq!(result.ranges, [5..8, 9..10]);
+
+        // the clusters of the former segments are counted as one
+        let result = matcher
+            .fuzzy_result("e\u{301}/cafe\u{301} b", "e/b")
+            .unwrap();
+        assert_eq!(result.indices, [0, 7]);
+        assert_eq!(result.ranges, [0..3, 11..12]);
+    }
+
+    #[test]
+    fn test_path_quality() {
+        let matcher = PathMatcher::new(SkimMatcherV2::default());
+        // basename first
+        assert_order(&matcher, "bar", &["src/foo/bar.rs", "src/bar/foo.rs"]);
+        assert_order(&matcher, "foo", &["bar/foo", "foo/bar"]);
+        // fewer segments
+        assert_order(&matcher, "ab", &["x/ab.rs", "a/b.rs"]);
+        assert_order(&matcher, "abc", &["x/abc", "a/bc", "a/b/c"]);
+    }
+
+    #[test]
+    fn test_path_indices() {
+        let matcher = PathMatcher::new(SkimMatcherV2::default());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "bar/src/bar.rs", "bar").unwrap(),
+            "bar/src/[b][a][r].rs"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "文档/bar.rs", "bar").unwrap(),
+            "文档/[b][a][r].rs"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "src\\foo\\bar.rs", "fb").unwrap(),
+            "src\\[f]oo\\[b]ar.rs"
+        );
+        assert_eq!(matcher.fuzzy_match("src/main.rs", ""), Some(0));
+        assert!(matcher.fuzzy_match("src/main.rs", "x").is_none());
+    }
+
+    #[test]
+    fn test_anchored() {
+        let matcher = PathMatcher::new(SkimMatcherV2::default());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "src/bin/main.rs", "src/main").unwrap(),
+            "[s][r][c]/bin/[m][a][i][n].rs"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "src/bin/main.rs", "/bin/").unwrap(),
+            "src/[b][i][n]/main.rs"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "x/a/b", "a/b").unwrap(),
+            "x/[a]/[b]"
+        );
+        // the later piece prefers the basename
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "a/b/x/b", "a/b").unwrap(),
+            "[a]/b/x/[b]"
+        );
+
+        // the pieces must be in different segments, in order
+        assert!(matcher.fuzzy_match("src/main.rs", "main/src").is_none());
+        assert!(matcher.fuzzy_match("srcmain.rs", "src/main").is_none());
+        assert!(matcher.fuzzy_match("src/main.rs", "s/m/r").is_none());
+
+        // nothing but separators
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "src/main.rs", "/").unwrap(),
+            "src[/]main.rs"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Sort the matched choices by a list of criteria, like the `--tiebreak` option of skim and fzf.
+//!
+//! The criteria are applied in order, the later ones only break the ties of the former ones,
+//! and the index of the choice always breaks the final ties, so the order is deterministic.
+//!
+//! | Criterion | Prefers                                                       |
+//! |-----------|---------------------------------------------------------------|
+//! | `score`   | the higher score                                              |
+//! | `length`  | the shorter choice, ignoring leading and trailing white spaces |
+//! | `begin`   | the match beginning earlier                                   |
+//! | `end`     | the match ending closer to the end of the choice              |
+//! | `index`   | the choice appearing earlier in the input                     |
+//!
+//! A criterion prefixed by `-` is reversed, e.g. `-index` prefers the later choices.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::rank::Ranking;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = SkimMatcherV2::default();
+//! let ranking = Ranking::parse("score,length").unwrap();
+//! let choices = ["foo bar", "foo", "xfoo"];
+//! let ranked = ranking.rank(&matcher, &choices, "foo");
+//!
+//! let sorted: Vec<&str> = ranked.iter().map(|(idx, _)| choices[*idx]).collect();
+//! assert_eq!(sorted, ["foo", "foo bar", "xfoo"]);
+//! ```
+
+use crate::{FuzzyMatcher, MatchResult};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Criterion {
+    Score,
+    Length,
+    Begin,
+    End,
+    Index,
+}
+
+impl Criterion {
+    pub fn parse(criterion: &str) -> Option<Self> {
+        match criterion.trim() {
+            "score" => Some(Criterion::Score),
+            "length" => Some(Criterion::Length),
+            "begin" => Some(Criterion::Begin),
+            "end" => Some(Criterion::End),
+            "index" => Some(Criterion::Index),
+            _ => None,
+        }
+    }
+
+    /// The value of the matched choice on this criterion, the smaller the better
+    #[allow(clippy::unnecessary_cast)]
+    fn value(self, choice: &str, result: &MatchResult, index: usize) -> i64 {
+        match self {
+            Criterion::Score => -(result.score as i64),
+            Criterion::Length => choice.trim().chars().count() as i64,
+            Criterion::Begin => result.indices.first().map_or(0, |&idx| idx as i64),
+            Criterion::End => result
+                .ranges
+                .last()
+                .map_or(0, |range| choice[range.end..].chars().count() as i64),
+            Criterion::Index => index as i64,
+        }
+    }
+}
+
+/// The key to sort the matched choices by, in ascending order
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone)]
+pub struct RankKey {
+    values: Vec<i64>,
+    index: usize,
+}
+
+/// A list of criteria to sort the matched choices by
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Ranking {
+    /// criteria with whether they are reversed
+    criteria: Vec<(Criterion, bool)>,
+}
+
+impl Default for Ranking {
+    /// The higher score first
+    fn default() -> Self {
+        Self::new().then(Criterion::Score)
+    }
+}
+
+impl Ranking {
+    /// A ranking without criteria, i.e. the choices are sorted by their indices
+    pub fn new() -> Self {
+        Self {
+            criteria: Vec::new(),
+        }
+    }
+
+    /// The default ranking of fzf, i.e. `--tiebreak=length`
+    pub fn fzf() -> Self {
+        Self::new().then(Criterion::Score).then(Criterion::Length)
+    }
+
+    /// Parse a comma separated list of criteria, e.g. `score,-begin,index`, return None if any
+    /// of them is unknown.
+    pub fn parse(criteria: &str) -> Option<Self> {
+        let mut ranking = Self::new();
+        for criterion in criteria.split(',') {
+            let criterion = criterion.trim();
+            ranking = match criterion.strip_prefix('-') {
+                Some(criterion) => ranking.then_reversed(Criterion::parse(criterion)?),
+                None => ranking.then(Criterion::parse(criterion)?),
+            };
+        }
+        Some(ranking)
+    }
+
+    /// Break the ties so far by `criterion`
+    pub fn then(mut self, criterion: Criterion) -> Self {
+        self.criteria.push((criterion, false));
+        self
+    }
+
+    /// Break the ties so far by `criterion` reversed
+    pub fn then_reversed(mut self, criterion: Criterion) -> Self {
+        self.criteria.push((criterion, true));
+        self
+    }
+
+    /// The sort key of a matched choice, `index` is the index of the choice in the input
+    pub fn key(&self, choice: &str, result: &MatchResult, index: usize) -> RankKey {
+        let values = self
+            .criteria
+            .iter()
+            .map(|&(criterion, reversed)| {
+                let value = criterion.value(choice, result, index);
+                if reversed {
+                    -value
+                } else {
+                    value
+                }
+            })
+            .collect();
+        RankKey { values, index }
+    }
+
+    /// Sort the matched choices, given as `(index of the choice, result)`, best first
+    pub fn sort<S: AsRef<str>>(&self, choices: &[S], matches: &mut [(usize, MatchResult)]) {
+        matches.sort_by_cached_key(|(index, result)| {
+            self.key(choices[*index].as_ref(), result, *index)
+        });
+    }
+
+    /// Match all the choices and return the matched ones in order, best first
+    pub fn rank<S: AsRef<str>>(
+        &self,
+        matcher: &dyn FuzzyMatcher,
+        choices: &[S],
+        pattern: &str,
+    ) -> Vec<(usize, MatchResult)> {
+        let mut matches: Vec<(usize, MatchResult)> = choices
+            .iter()
+            .enumerate()
+            .filter_map(|(index, choice)| {
+                matcher
+                    .fuzzy_result(choice.as_ref(), pattern)
+                    .map(|result| (index, result))
+            })
+            .collect();
+        self.sort(choices, &mut matches);
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skim::SkimMatcherV2;
+
+    fn sorted(ranking: &Ranking, choices: &[&str], pattern: &str) -> Vec<String> {
+        let matcher = SkimMatcherV2::default();
+        ranking
+            .rank(&matcher, choices, pattern)
+            .into_iter()
+            .map(|(idx, _)| choices[idx].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ranking::parse("score"), Some(Ranking::default()));
+        assert_eq!(Ranking::parse("score, length"), Some(Ranking::fzf()));
+        assert_eq!(
+            Ranking::parse("-begin,index"),
+            Some(
+                Ranking::new()
+                    .then_reversed(Criterion::Begin)
+                    .then(Criterion::Index)
+            )
+        );
+        assert_eq!(Ranking::parse("score,foo"), None);
+        assert_eq!(Ranking::parse(""), None);
+    }
+
+    #[test]
+    fn test_criteria() {
+        // all the same scores
+        let choices = ["xxab", "ab", "xab", " ab "];
+        assert_eq!(
+            sorted(&Ranking::new(), &choices, "ab"),
+            ["xxab", "ab", "xab", " ab "]
+        );
+        assert_eq!(
+            sorted(&Ranking::parse("-index").unwrap(), &choices, "ab"),
+            [" ab ", "xab", "ab", "xxab"]
+        );
+        assert_eq!(
+            sorted(&Ranking::parse("length").unwrap(), &choices, "ab"),
+            ["ab", " ab ", "xab", "xxab"]
+        );
+        assert_eq!(
+            sorted(&Ranking::parse("begin").unwrap(), &choices, "ab"),
+            ["ab", "xab", " ab ", "xxab"]
+        );
+        assert_eq!(
+            sorted(&Ranking::parse("end,-begin").unwrap(), &choices, "ab"),
+            ["xxab", "xab", "ab", " ab "]
+        );
+    }
+
+    #[test]
+    fn test_score_first() {
+        let choices = ["a_b", "ab", "axxb"];
+        let ranking = Ranking::default();
+        assert_eq!(sorted(&ranking, &choices, "ab"), ["ab", "a_b", "axxb"]);
+        assert_eq!(
+            sorted(&Ranking::parse("-score").unwrap(), &choices, "ab"),
+            ["axxb", "a_b", "ab"]
+        );
+    }
+
+    #[test]
+    fn test_fzf_ranking() {
+        let matcher = SkimMatcherV2::default().fzf_compat();
+        let choices = ["xfooy", " foo ", "foo bar", "afoo", "bfoo"];
+        let ranked: Vec<&str> = Ranking::fzf()
+            .rank(&matcher, &choices, "foo")
+            .into_iter()
+            .map(|(idx, _)| choices[idx])
+            .collect();
+        // the higher score, then the shorter one ignoring the spaces, then the earlier one
+        assert_eq!(ranked, [" foo ", "foo bar", "afoo", "bfoo", "xfooy"]);
+    }
+}
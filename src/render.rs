@@ -0,0 +1,364 @@
+//! Render a matched choice into a limited number of terminal columns, like skim does.
+//!
+//! The width of every character is its display width, i.e. East Asian wide characters take
+//! two columns and combining marks take none, and tabs are expanded to spaces up to the next
+//! tab stop. If the choice doesn't fit, it is truncated with ellipses while the matched
+//! characters are kept visible: the head of the choice is shown if the matches are in it,
+//! then the tail, otherwise the window is centered on the matches.
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::render::{Renderer, Span, SpanStyle};
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let choice = "src/components/widgets/button.rs";
+//! let (_score, indices) = SkimMatcherV2::default().fuzzy_indices(choice, "button").unwrap();
+//!
+//! let spans = Renderer::default().render(choice, &indices, 12);
+//! assert_eq!(
+//!     spans,
+//!     [
+//!         Span::new("..", SpanStyle::Ellipsis),
+//!         Span::new("/", SpanStyle::Normal),
+//!         Span::new("button", SpanStyle::Matched),
+//!         Span::new(".rs", SpanStyle::Normal),
+//!     ]
+//! );
+//! ```
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::IndexType;
+
+const DEFAULT_TAB_WIDTH: usize = 8;
+const DEFAULT_ELLIPSIS: &str = "..";
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum SpanStyle {
+    /// characters that are not matched
+    Normal,
+    /// matched characters
+    Matched,
+    /// the ellipsis standing for the truncated characters
+    Ellipsis,
+}
+
+/// A piece of the rendered choice, the consecutive characters of the same style are merged
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+impl Span {
+    pub fn new(text: &str, style: SpanStyle) -> Self {
+        Self {
+            text: text.to_string(),
+            style,
+        }
+    }
+}
+
+/// A unit of the choice, i.e. a character or a grapheme cluster, placed on the columns
+struct Unit<'a> {
+    text: &'a str,
+    /// the column it starts at
+    start: usize,
+    width: usize,
+    matched: bool,
+}
+
+impl Unit<'_> {
+    fn end(&self) -> usize {
+        self.start + self.width
+    }
+}
+
+/// Renders the matched choices, see the module documentation
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    tab_width: usize,
+    ellipsis: String,
+    graphemes: bool,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            ellipsis: DEFAULT_ELLIPSIS.to_string(),
+            graphemes: false,
+        }
+    }
+}
+
+impl Renderer {
+    /// The columns between the tab stops, defaults to 8.
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// The text standing for the truncated characters, defaults to "..".
+    pub fn ellipsis(mut self, ellipsis: &str) -> Self {
+        self.ellipsis = ellipsis.to_string();
+        self
+    }
+
+    /// Take the indices as the ones of extended grapheme clusters, i.e. the ones returned by
+    /// the matchers with `graphemes(true)`.
+    pub fn graphemes(mut self, graphemes: bool) -> Self {
+        self.graphemes = graphemes;
+        self
+    }
+
+    /// The number of columns the text takes, with the tabs expanded
+    pub fn width(&self, text: &str) -> usize {
+        self.units(text, &[]).last().map_or(0, Unit::end)
+    }
+
+    /// Render the choice with the (sorted) indices of the matched characters into at most
+    /// `width` columns.
+    pub fn render(&self, choice: &str, indices: &[IndexType], width: usize) -> Vec<Span> {
+        let units = self.units(choice, indices);
+        let total = units.last().map_or(0, Unit::end);
+        if total <= width {
+            return spans(&units, 0, total, None, None);
+        }
+
+        // drop the ellipses if there is no room for them
+        let ellipsis = if width > 2 * self.ellipsis.width() {
+            self.ellipsis.as_str()
+        } else {
+            ""
+        };
+        let ellipsis_width = ellipsis.width();
+
+        // the columns the matches span over
+        let first = units.iter().find(|unit| unit.matched);
+        let last = units.iter().rev().find(|unit| unit.matched);
+        let (match_start, match_end) = match (first, last) {
+            (Some(first), Some(last)) => (first.start, last.end()),
+            _ => (0, 0),
+        };
+
+        if match_end + ellipsis_width <= width {
+            spans(&units, 0, width - ellipsis_width, None, Some(ellipsis))
+        } else if total - match_start + ellipsis_width <= width {
+            spans(
+                &units,
+                total - (width - ellipsis_width),
+                total,
+                Some(ellipsis),
+                None,
+            )
+        } else {
+            let available = width - 2 * ellipsis_width;
+            let start = if match_end - match_start <= available {
+                let start = ((match_start + match_end) / 2).saturating_sub(available / 2);
+                // don't waste the columns on a wide character split by the window
+                match units
+                    .iter()
+                    .find(|unit| unit.start < start && start < unit.end())
+                {
+                    Some(unit) if unit.start + available >= match_end => unit.start,
+                    Some(unit) => unit.end(),
+                    None => start,
+                }
+            } else {
+                match_start
+            };
+            spans(
+                &units,
+                start,
+                start + available,
+                Some(ellipsis),
+                Some(ellipsis),
+            )
+        }
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    fn units<'a>(&self, text: &'a str, indices: &[IndexType]) -> Vec<Unit<'a>> {
+        let texts: Box<dyn Iterator<Item = &'a str>> = if self.graphemes {
+            Box::new(text.graphemes(true))
+        } else {
+            Box::new(
+                text.char_indices()
+                    .map(move |(offset, ch)| &text[offset..offset + ch.len_utf8()]),
+            )
+        };
+
+        let mut units = Vec::new();
+        let mut column = 0;
+        let mut peekable = indices.iter().peekable();
+        for (idx, text) in texts.enumerate() {
+            let matched = peekable
+                .next_if(|&&next_idx| next_idx as usize == idx)
+                .is_some();
+            let width = if text == "\t" {
+                self.tab_width - column % self.tab_width.max(1)
+            } else {
+                text.width()
+            };
+            units.push(Unit {
+                text,
+                start: column,
+                width,
+                matched,
+            });
+            column += width;
+        }
+        units
+    }
+}
+
+/// The spans of the units within the columns `[start, end)`, zero width units go along
+/// with the unit before them.
+fn spans(
+    units: &[Unit],
+    start: usize,
+    end: usize,
+    left_ellipsis: Option<&str>,
+    right_ellipsis: Option<&str>,
+) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    if let Some(ellipsis) = left_ellipsis.filter(|ellipsis| !ellipsis.is_empty()) {
+        spans.push(Span::new(ellipsis, SpanStyle::Ellipsis));
+    }
+
+    let mut last_visible = start == 0;
+    for unit in units {
+        // tabs are spaces, so they could be partially visible
+        let is_tab = unit.text == "\t";
+        let visible = if unit.width == 0 {
+            last_visible
+        } else if is_tab {
+            unit.start < end && unit.end() > start
+        } else {
+            unit.start >= start && unit.end() <= end
+        };
+        last_visible = visible;
+        if !visible {
+            continue;
+        }
+
+        let style = if unit.matched {
+            SpanStyle::Matched
+        } else {
+            SpanStyle::Normal
+        };
+        if spans.last().map(|span| span.style) != Some(style) {
+            spans.push(Span {
+                text: String::new(),
+                style,
+            });
+        }
+        let span = spans.last_mut().unwrap();
+        if is_tab {
+            let spaces = unit.end().min(end) - unit.start.max(start);
+            span.text.push_str(&" ".repeat(spaces));
+        } else {
+            span.text.push_str(unit.text);
+        }
+    }
+
+    if let Some(ellipsis) = right_ellipsis.filter(|ellipsis| !ellipsis.is_empty()) {
+        spans.push(Span::new(ellipsis, SpanStyle::Ellipsis));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Show the spans as plain text, the matched ones in brackets
+    fn show(spans: &[Span]) -> String {
+        spans
+            .iter()
+            .map(|span| match span.style {
+                SpanStyle::Matched => format!("[{}]", span.text),
+                _ => span.text.clone(),
+            })
+            .collect()
+    }
+
+    fn render(choice: &str, indices: &[IndexType], width: usize) -> String {
+        show(&Renderer::default().render(choice, indices, width))
+    }
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(render("abc", &[1], 10), "a[b]c");
+        assert_eq!(render("abc", &[0, 1, 2], 3), "[abc]");
+        assert_eq!(render("", &[], 3), "");
+        assert_eq!(
+            Renderer::default().render("abc", &[1], 10),
+            [
+                Span::new("a", SpanStyle::Normal),
+                Span::new("b", SpanStyle::Matched),
+                Span::new("c", SpanStyle::Normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        let choice = "0123456789abcdefghij";
+        // the head
+        assert_eq!(render(choice, &[1, 2], 10), "0[12]34567..");
+        assert_eq!(render(choice, &[], 10), "01234567..");
+        // the tail
+        assert_eq!(render(choice, &[18], 10), "..cdefgh[i]j");
+        // centered
+        assert_eq!(render(choice, &[10, 11], 10), "..89[ab]cd..");
+        assert_eq!(render(choice, &[9, 12], 10), "..8[9]ab[c]d..");
+        // the matches don't fit, keep the start of them
+        assert_eq!(render(choice, &[5, 15], 10), "..[5]6789a..");
+        // no room for the ellipses
+        assert_eq!(render(choice, &[10], 4), "89[a]b");
+        assert_eq!(
+            show(&Renderer::default().ellipsis("…").render(choice, &[10], 5)),
+            "…9[a]b…"
+        );
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        let renderer = Renderer::default();
+        assert_eq!(renderer.width("中文abc"), 7);
+        assert_eq!(renderer.width("e\u{301}"), 1);
+
+        // 16 columns, the wide characters are never split
+        let choice = "中文中文中文中文";
+        assert_eq!(render(choice, &[0], 7), "[中]文..");
+        assert_eq!(render(choice, &[7], 7), "..中[文]");
+        assert_eq!(render(choice, &[4], 8), "..文[中]..");
+
+        // combining marks go along with their base characters
+        assert_eq!(render("xxxxe\u{301}yyyy", &[4], 6), "..x[e]\u{301}..");
+        assert_eq!(render("e\u{301}yyyyyyyy", &[9], 5), "..yy[y]");
+    }
+
+    #[test]
+    fn test_tabs() {
+        let renderer = Renderer::default().tab_width(4);
+        assert_eq!(renderer.width("a\tb"), 5);
+        assert_eq!(renderer.width("abcd\tb"), 9);
+        assert_eq!(show(&renderer.render("a\tb", &[2], 10)), "a   [b]");
+        assert_eq!(show(&renderer.render("\t\tab", &[2], 6)), "..  [a]b");
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let renderer = Renderer::default().graphemes(true);
+        assert_eq!(
+            show(&renderer.render("cafe\u{301} bar", &[3, 5], 20)),
+            "caf[e\u{301}] [b]ar"
+        );
+        assert_eq!(show(&renderer.render("👍🏽 ok", &[0, 2], 20)), "[👍🏽] [o]k");
+    }
+}
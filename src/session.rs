@@ -0,0 +1,219 @@
+//! Stateful matching for interactive pickers where the query is typed character by character.
+//!
+//! If a choice doesn't match a pattern, it can't match any pattern extended from it. So when
+//! the new query extends the last one, only the choices matched last time are scored again.
+//! On deletion or other edits, all the choices are scanned.
+//!
+//! Note that this relies on the matcher never matching a choice with an extended pattern if it
+//! doesn't match the shorter one, which the matcher tells by `FuzzyMatcher::narrows`, otherwise
+//! all the choices are scanned on every query. It holds for `ClangdMatcher`, `FzfV1Matcher` and
+//! `SkimMatcherV2` without `min_score` and `max_typos`, but NOT for `ExtendedMatcher`, e.g.
+//! "a |" extended to "a | b", or "!a" extended to "!ab".
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::session::MatchSession;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let mut session = MatchSession::new(SkimMatcherV2::default(), vec!["abc", "axbycz", "xyz"]);
+//! assert_eq!(session.query("a").len(), 2);
+//! assert_eq!(session.query("ab").len(), 2); // only "abc" and "axbycz" are scored
+//! assert_eq!(session.query("abz").len(), 1);
+//! assert_eq!(session.query("x").len(), 2); // not extended, scan all choices again
+//! ```
+
+use crate::batch::BatchMatch;
+use crate::FuzzyMatcher;
+
+pub struct MatchSession<M: FuzzyMatcher, S: AsRef<str>> {
+    matcher: M,
+    choices: Vec<S>,
+
+    pattern: String,
+    matches: Vec<BatchMatch>,
+    num_scanned: usize, // choices after this are not matched with `pattern` yet
+}
+
+impl<M: FuzzyMatcher, S: AsRef<str>> MatchSession<M, S> {
+    pub fn new(matcher: M, choices: Vec<S>) -> Self {
+        Self {
+            matcher,
+            choices,
+            pattern: String::new(),
+            matches: Vec::new(),
+            num_scanned: 0,
+        }
+    }
+
+    /// Add more choices, e.g. when they are read in the background.
+    /// They'll be matched on the next query.
+    pub fn push(&mut self, choice: S) {
+        self.choices.push(choice);
+    }
+
+    pub fn choices(&self) -> &[S] {
+        &self.choices
+    }
+
+    pub fn matcher(&self) -> &M {
+        &self.matcher
+    }
+
+    /// The pattern of the last query
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The matches of the last query, in the order of the choices
+    pub fn matches(&self) -> &[BatchMatch] {
+        &self.matches
+    }
+
+    /// Match the choices with the pattern, return the matches in the order of the choices.
+    /// The matched indices are not calculated.
+    pub fn query(&mut self, pattern: &str) -> &[BatchMatch] {
+        let mut matches = Vec::new();
+
+        if self.matcher.narrows() && pattern.starts_with(self.pattern.as_str()) {
+            // narrowing: only the choices that matched last time could still match
+            for prev in self.matches.iter() {
+                self.match_choice(prev.index, pattern, &mut matches);
+            }
+        } else {
+            for idx in 0..self.num_scanned {
+                self.match_choice(idx, pattern, &mut matches);
+            }
+        }
+
+        for idx in self.num_scanned..self.choices.len() {
+            self.match_choice(idx, pattern, &mut matches);
+        }
+
+        self.pattern = pattern.to_string();
+        self.matches = matches;
+        self.num_scanned = self.choices.len();
+        &self.matches
+    }
+
+    fn match_choice(&self, index: usize, pattern: &str, matches: &mut Vec<BatchMatch>) {
+        if let Some(score) = self
+            .matcher
+            .fuzzy_match(self.choices[index].as_ref(), pattern)
+        {
+            matches.push(BatchMatch {
+                index,
+                score,
+                indices: Vec::new(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clangd::ClangdMatcher;
+    use crate::skim::SkimMatcherV2;
+    use crate::{IndexType, ScoreType};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// count the choices being scored
+    struct CountingMatcher {
+        matcher: SkimMatcherV2,
+        count: AtomicUsize,
+    }
+
+    impl FuzzyMatcher for CountingMatcher {
+        fn fuzzy_indices(
+            &self,
+            choice: &str,
+            pattern: &str,
+        ) -> Option<(ScoreType, Vec<IndexType>)> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.matcher.fuzzy_indices(choice, pattern)
+        }
+
+        fn narrows(&self) -> bool {
+            self.matcher.narrows()
+        }
+    }
+
+    fn full_scan(matcher: &dyn FuzzyMatcher, choices: &[&str], pattern: &str) -> Vec<BatchMatch> {
+        choices
+            .iter()
+            .enumerate()
+            .filter_map(|(index, choice)| {
+                let score = matcher.fuzzy_match(choice, pattern)?;
+                Some(BatchMatch {
+                    index,
+                    score,
+                    indices: Vec::new(),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_full_scan() {
+        let choices = vec!["abc", "axbycz", "xyz", "aBc", "abxcx", "cba", "a_b_c"];
+        let queries = ["", "a", "ab", "abc", "ab", "aB", "aBc", "x", "xc", "", "c"];
+
+        let mut session = MatchSession::new(SkimMatcherV2::default(), choices.clone());
+        for query in queries.iter() {
+            let expected = full_scan(session.matcher(), &choices, query);
+            assert_eq!(session.query(query), &expected[..], "query: {}", query);
+        }
+
+        let mut session = MatchSession::new(ClangdMatcher::default(), choices.clone());
+        for query in queries.iter() {
+            let expected = full_scan(session.matcher(), &choices, query);
+            assert_eq!(session.query(query), &expected[..], "query: {}", query);
+        }
+    }
+
+    #[test]
+    fn test_not_narrowing() {
+        // "a" scores 31 on "ab", below the threshold, while "ab" scores 51
+        let matcher = SkimMatcherV2::default().min_score(40);
+        assert!(!matcher.narrows());
+        let mut session = MatchSession::new(matcher, vec!["ab"]);
+        assert!(session.query("a").is_empty());
+        assert_eq!(session.query("ab").len(), 1);
+
+        // "abc" matches "xbc" with a typo, "ab" is too short for one
+        let mut session = MatchSession::new(SkimMatcherV2::default().max_typos(1), vec!["xbc"]);
+        assert!(session.query("ab").is_empty());
+        assert_eq!(session.query("abc").len(), 1);
+    }
+
+    #[test]
+    fn test_narrowing() {
+        let matcher = CountingMatcher {
+            matcher: SkimMatcherV2::default(),
+            count: AtomicUsize::new(0),
+        };
+        let choices = vec!["abc", "axbycz", "xyz", "cba"];
+        let mut session = MatchSession::new(matcher, choices);
+        let scored = |session: &MatchSession<CountingMatcher, &str>| {
+            session.matcher().count.swap(0, Ordering::SeqCst)
+        };
+
+        assert_eq!(session.query("a").len(), 3);
+        assert_eq!(scored(&session), 4);
+
+        assert_eq!(session.query("ab").len(), 2);
+        assert_eq!(scored(&session), 3);
+
+        assert_eq!(session.query("abc").len(), 2);
+        assert_eq!(scored(&session), 2);
+
+        // deletion: scan all
+        assert_eq!(session.query("ab").len(), 2);
+        assert_eq!(scored(&session), 4);
+
+        // new choices are always scanned
+        session.push("ab");
+        assert_eq!(session.query("abc").len(), 2);
+        assert_eq!(scored(&session), 3);
+    }
+}
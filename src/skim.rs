@@ -1,29 +1,38 @@
+//! The fuzzy matching algorithm used by skim
+//!
+//! # Example:
+//! ```edition2018
+//! use fuzzy_matcher::FuzzyMatcher;
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//!
+//! let matcher = SkimMatcherV2::default();
+//! assert_eq!(None, matcher.fuzzy_match("abc", "abx"));
+//! assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
+//! assert!(matcher.fuzzy_match("axbycz", "xyz").is_some());
+//!
+//! let (score, indices) = matcher.fuzzy_indices("axbycz", "abc").unwrap();
+//! assert_eq!(indices, [0, 2, 4]);
+//! ```
+
 #![allow(deprecated)]
 
 use std::cell::RefCell;
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::collections::HashSet;
 use std::fmt::Formatter;
 
-use thread_local::CachedThreadLocal;
+use thread_local::ThreadLocal;
 
+use crate::classifier::{CharClassifier, CharType, SkimClassifier};
+use crate::explain::{Contribution, Explanation, MatchedChar, ScoreKind};
+use crate::fzf;
 use crate::skim::Movement::{Match, Skip};
-use crate::util::{char_equal, cheap_matches};
-///! The fuzzy matching algorithm used by skim
-///!
-///! # Example:
-///! ```edition2018
-///! use fuzzy_matcher::FuzzyMatcher;
-///! use fuzzy_matcher::skim::SkimMatcherV2;
-///!
-///! let matcher = SkimMatcherV2::default();
-///! assert_eq!(None, matcher.fuzzy_match("abc", "abx"));
-///! assert!(matcher.fuzzy_match("axbycz", "abc").is_some());
-///! assert!(matcher.fuzzy_match("axbycz", "xyz").is_some());
-///!
-///! let (score, indices) = matcher.fuzzy_indices("axbycz", "abc").unwrap();
-///! assert_eq!(indices, [0, 2, 4]);
-///! ```
-use crate::{FuzzyMatcher, IndexType, ScoreType};
+use crate::util::{
+    char_equal, cheap_matches, cheap_matches_ascii, contains_upper, contains_upper_atoms,
+    count_units, extend_units, is_ascii_units, map_original_indices, matched_prefix_len, may_match,
+    transform_chars, typo_variants, WILDCARD,
+};
+use crate::{CaseFolding, FuzzyMatcher, IndexType, ScoreType};
 
 const BONUS_MATCHED: ScoreType = 4;
 const BONUS_CASE_MATCH: ScoreType = 4;
@@ -39,15 +48,9 @@ const PENALTY_MAX_LEADING: ScoreType = -18;
 const PENALTY_UNMATCHED: ScoreType = -2;
 
 #[deprecated(since = "0.3.5", note = "Please use SkimMatcherV2 instead")]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SkimMatcher {}
 
-impl Default for SkimMatcher {
-    fn default() -> Self {
-        Self {}
-    }
-}
-
 /// The V1 matcher is based on ForrestTheWoods's post
 /// https://www.forrestthewoods.com/blog/reverse_engineering_sublime_texts_fuzzy_match/
 ///
@@ -80,6 +83,7 @@ pub fn fuzzy_match(choice: &str, pattern: &str) -> Option<ScoreType> {
 }
 
 #[deprecated(since = "0.3.5", note = "Please use SkimMatcherV2 instead")]
+#[allow(clippy::unnecessary_cast)]
 pub fn fuzzy_indices(choice: &str, pattern: &str) -> Option<(ScoreType, Vec<IndexType>)> {
     if pattern.is_empty() {
         return Some((0, Vec::new()));
@@ -126,6 +130,7 @@ impl Default for MatchingStatus {
     }
 }
 
+#[allow(clippy::unnecessary_cast)]
 fn build_graph(choice: &str, pattern: &str) -> Option<Vec<Vec<MatchingStatus>>> {
     let mut scores = vec![];
 
@@ -137,7 +142,7 @@ fn build_graph(choice: &str, pattern: &str) -> Option<Vec<Vec<MatchingStatus>>>
         let mut vec = vec![];
         let mut choice_prev_ch = '\0';
         for (idx, ch) in choice.chars().enumerate() {
-            if ch.to_ascii_lowercase() == pat_ch.to_ascii_lowercase() && idx >= match_start_idx {
+            if ch.eq_ignore_ascii_case(&pat_ch) && idx >= match_start_idx {
                 let score = fuzzy_score(
                     ch,
                     idx as IndexType,
@@ -239,7 +244,7 @@ fn fuzzy_score(
 ) -> ScoreType {
     let mut score = BONUS_MATCHED;
 
-    let choice_prev_ch_type = CharType::of(choice_prev_ch);
+    let choice_prev_ch_type = SkimClassifier.char_type(choice_prev_ch);
     let choice_role = CharRole::of(choice_prev_ch, choice_ch);
 
     if pat_ch == choice_ch {
@@ -345,6 +350,14 @@ enum Movement {
 /// Inner state of the score matrix
 // Implementation detail: tried to pad to 16B
 // will store the m and p matrix together
+/// The best match of the variants of a pattern with typos, see `max_typos`
+struct TypoMatch {
+    variant: Vec<char>,
+    typos: usize,
+    score: ScoreType,
+    indices: Vec<IndexType>,
+}
+
 #[derive(Clone, Debug)]
 struct MatrixCell {
     pub m_move: Movement,
@@ -357,7 +370,9 @@ struct MatrixCell {
     pub bonus: i32,
 }
 
-const MATRIX_CELL_NEG_INFINITY: i32 = std::i16::MIN as i32;
+const MATRIX_CELL_NEG_INFINITY: i32 = i16::MIN as i32;
+const DEFAULT_TYPO_PENALTY: ScoreType = 32;
+const MAX_TYPOS: usize = 3;
 
 impl Default for MatrixCell {
     fn default() -> Self {
@@ -470,37 +485,6 @@ impl<'a> std::fmt::Debug for ScoreMatrix<'a> {
     }
 }
 
-/// We categorize characters into types:
-///
-/// - Empty(E): the start of string
-/// - Upper(U): the ascii upper case
-/// - lower(L): the ascii lower case & other unicode characters
-/// - number(N): ascii number
-/// - hard separator(S): clearly separate the content: ` ` `/` `\` `|` `(` `) `[` `]` `{` `}`
-/// - soft separator(s): other ascii punctuation, e.g. `!` `"` `#` `$`, ...
-#[derive(Debug, PartialEq, Copy, Clone)]
-enum CharType {
-    Empty,
-    Upper,
-    Lower,
-    Number,
-    HardSep,
-    SoftSep,
-}
-
-impl CharType {
-    pub fn of(ch: char) -> Self {
-        match ch {
-            '\0' => CharType::Empty,
-            ' ' | '/' | '\\' | '|' | '(' | ')' | '[' | ']' | '{' | '}' => CharType::HardSep,
-            '!'..='\'' | '*'..='.' | ':'..='@' | '^'..='`' | '~' => CharType::SoftSep,
-            '0'..='9' => CharType::Number,
-            'A'..='Z' => CharType::Upper,
-            _ => CharType::Lower,
-        }
-    }
-}
-
 /// Ref: https://github.com/llvm-mirror/clang-tools-extra/blob/master/clangd/FuzzyMatch.cpp
 ///
 ///
@@ -529,7 +513,10 @@ enum CharRole {
 
 impl CharRole {
     pub fn of(prev: char, cur: char) -> Self {
-        Self::of_type(CharType::of(prev), CharType::of(cur))
+        Self::of_type(
+            SkimClassifier.char_type(prev),
+            SkimClassifier.char_type(cur),
+        )
     }
     pub fn of_type(prev: CharType, cur: CharType) -> Self {
         match (prev, cur) {
@@ -606,12 +593,20 @@ pub struct SkimMatcherV2 {
 
     score_config: SkimScoreConfig,
     element_limit: usize,
+    min_score: Option<ScoreType>,
+    max_typos: usize,
+    typo_penalty: ScoreType,
     case: CaseMatching,
+    case_folding: CaseFolding,
+    normalize: bool,
+    graphemes: bool,
+    classifier: Box<dyn CharClassifier>,
+    fzf_compat: bool,
     use_cache: bool,
 
-    m_cache: CachedThreadLocal<RefCell<Vec<MatrixCell>>>,
-    c_cache: CachedThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of choice
-    p_cache: CachedThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of pattern
+    m_cache: ThreadLocal<RefCell<Vec<MatrixCell>>>,
+    c_cache: ThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of choice
+    p_cache: ThreadLocal<RefCell<Vec<char>>>, // vector to store the characters of pattern
 }
 
 impl Default for SkimMatcherV2 {
@@ -620,12 +615,20 @@ impl Default for SkimMatcherV2 {
             debug: false,
             score_config: SkimScoreConfig::default(),
             element_limit: 0,
+            min_score: None,
+            max_typos: 0,
+            typo_penalty: DEFAULT_TYPO_PENALTY,
             case: CaseMatching::Smart,
+            case_folding: CaseFolding::Ascii,
+            normalize: false,
+            graphemes: false,
+            classifier: Box::new(SkimClassifier),
+            fzf_compat: false,
             use_cache: true,
 
-            m_cache: CachedThreadLocal::new(),
-            c_cache: CachedThreadLocal::new(),
-            p_cache: CachedThreadLocal::new(),
+            m_cache: ThreadLocal::new(),
+            c_cache: ThreadLocal::new(),
+            p_cache: ThreadLocal::new(),
         }
     }
 }
@@ -641,6 +644,37 @@ impl SkimMatcherV2 {
         self
     }
 
+    /// Choices scored lower than `min_score` are treated as not matched, while an empty pattern
+    /// still matches everything. The matching stops as soon as the rest of the pattern can't
+    /// bring the score up to it anymore.
+    pub fn min_score(mut self, min_score: ScoreType) -> Self {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    /// Tolerate up to `max_typos` typos in the pattern when it doesn't match as is, a typo is
+    /// a pattern character that is mistyped or extra, or two adjacent characters swapped, e.g.
+    /// "fuzyz" matches "fuzzy". The matches with the fewest typos are taken, and every typo
+    /// costs `typo_penalty`. The indices are the ones of the aligned characters.
+    ///
+    /// A mistyped character matches any character of the choice in its place, e.g. "fuzzt"
+    /// highlights all of "fuzzy". More than half of the pattern has to match as is, i.e. a
+    /// pattern of `n` characters tolerates at most `(n - 1) / 2` typos, otherwise a short one
+    /// would match almost anything: "ab" never matches "xb".
+    ///
+    /// At most 3 typos are tolerated, as the variants of the pattern grow with the power of
+    /// the typos, though the ones that can't match are not expanded. Defaults to 0.
+    pub fn max_typos(mut self, max_typos: usize) -> Self {
+        self.max_typos = min(max_typos, MAX_TYPOS);
+        self
+    }
+
+    /// The penalty of every typo, see `max_typos`. Defaults to 32, i.e. two matched characters.
+    pub fn typo_penalty(mut self, typo_penalty: ScoreType) -> Self {
+        self.typo_penalty = typo_penalty;
+        self
+    }
+
     pub fn ignore_case(mut self) -> Self {
         self.case = CaseMatching::Ignore;
         self
@@ -656,6 +690,55 @@ impl SkimMatcherV2 {
         self
     }
 
+    /// Set how characters are folded in case insensitive matching, it also decides which
+    /// characters count as upper case for smart case.
+    pub fn case_folding(mut self, case_folding: CaseFolding) -> Self {
+        self.case_folding = case_folding;
+        self
+    }
+
+    /// Match the characters regardless of their diacritics, e.g. "resume" matches "résumé".
+    /// The indices still refer to the characters of the original choice.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Match the extended grapheme clusters instead of the characters, so that a visible glyph,
+    /// e.g. an emoji with modifiers or a letter with combining marks, is never split. A cluster
+    /// is matched by its first character, and the indices refer to the clusters, use
+    /// `MatchResult::from_graphemes` (or `fuzzy_result`) for their byte ranges.
+    ///
+    /// It only applies to the `&str` methods, the characters given to `fuzzy_indices_chars`
+    /// are taken as the units already. The wrappers of this crate, e.g. `PathMatcher`, index
+    /// the clusters as well.
+    pub fn graphemes(mut self, graphemes: bool) -> Self {
+        self.graphemes = graphemes;
+        self
+    }
+
+    /// Decide the word boundaries by a custom classification of characters, defaults to
+    /// `SkimClassifier`.
+    pub fn char_classifier<C: CharClassifier + 'static>(mut self, classifier: C) -> Self {
+        self.classifier = Box::new(classifier);
+        self
+    }
+
+    /// Score exactly like fzf's v2 algorithm, so the matches rank the same as in fzf: the
+    /// bonuses and the tie-breaking of fzf are used, `score_config` and `char_classifier` are
+    /// ignored. It also switches the case folding to `CaseFolding::Simple` as fzf lowercases
+    /// unicode characters, and turns on `normalize` as fzf does without `--literal`, set
+    /// `case_folding` or `normalize` afterwards to override them.
+    ///
+    /// If `element_limit` is exceeded, it falls back to fzf's v1 algorithm just like fzf does.
+    /// Sort the matches by `rank::Ranking::fzf` to break the ties the same way as fzf.
+    pub fn fzf_compat(mut self) -> Self {
+        self.fzf_compat = true;
+        self.case_folding = CaseFolding::Simple;
+        self.normalize = true;
+        self
+    }
+
     pub fn use_cache(mut self, use_cache: bool) -> Self {
         self.use_cache = use_cache;
         self
@@ -666,16 +749,17 @@ impl SkimMatcherV2 {
         self
     }
 
-    /// Build the score matrix using the algorithm described above
-    fn build_score_matrix(
+    /// Build the score matrix using the algorithm described above, return false if it stops
+    /// early because `min_score` can't be reached.
+    fn build_score_matrix<C: Copy + Into<char>>(
         &self,
         m: &mut ScoreMatrix,
-        choice: &[char],
-        pattern: &[char],
+        choice: &[C],
+        pattern: &[C],
         first_match_indices: &[usize],
         compressed: bool,
         case_sensitive: bool,
-    ) {
+    ) -> bool {
         let mut in_place_bonuses = vec![0; m.cols];
 
         self.build_in_place_bonus(choice, &mut in_place_bonuses);
@@ -692,13 +776,37 @@ impl SkimMatcherV2 {
             m[(0, j)].p_score = self.score_config.gap_extension;
         }
 
+        let max_score_per_char = self.max_score_per_char();
+
         // update the matrix;
         for (i, &p_ch) in pattern.iter().enumerate() {
+            let p_ch: char = p_ch.into();
             let row = self.adjust_row_idx(i + 1, compressed);
             let row_prev = self.adjust_row_idx(i, compressed);
             let to_skip = first_match_indices[i];
 
+            if let (Some(min_score), Some(max_score_per_char)) =
+                (self.min_score, max_score_per_char)
+            {
+                // only the cells after the previous row's first match are filled
+                let first_col = if i == 0 {
+                    0
+                } else {
+                    first_match_indices[i - 1] + 1
+                };
+                let best_so_far = m.get_row(row_prev)[first_col..]
+                    .iter()
+                    .map(|cell| max(cell.m_score, cell.p_score))
+                    .max()
+                    .unwrap_or(MATRIX_CELL_NEG_INFINITY);
+                let remaining = (pattern.len() - i) as ScoreType;
+                if best_so_far as ScoreType + remaining * max_score_per_char < min_score {
+                    return false;
+                }
+            }
+
             for (j, &c_ch) in choice[to_skip..].iter().enumerate() {
+                let c_ch: char = c_ch.into();
                 let col = to_skip + j + 1;
                 let col_prev = to_skip + j;
                 let idx_cur = m.get_index(row, col);
@@ -752,16 +860,40 @@ impl SkimMatcherV2 {
                 }
             }
         }
+
+        true
+    }
+
+    /// The upper bound of the score a pattern character could add to the best alignment so far,
+    /// None if there's no such bound, i.e. the gaps are rewarded.
+    fn max_score_per_char(&self) -> Option<ScoreType> {
+        let config = &self.score_config;
+        if config.gap_extension > 0 {
+            return None;
+        }
+
+        let max_match = config.score_match + max(0, config.penalty_case_mismatch);
+        // the bonus of consecutive matches is carried over from the former ones
+        let max_in_place = max(
+            0,
+            max(
+                config.bonus_head,
+                max(config.bonus_break, config.bonus_camel),
+            ),
+        ) * max(1, config.bonus_first_char_multiplier);
+        let max_bonus = max(config.bonus_consecutive, max_in_place);
+        let max_gap = max(0, config.gap_start + config.gap_extension);
+
+        Some((max_match + max_bonus + max_gap) as ScoreType)
     }
 
     /// check bonus for start of camel case, etc.
-    fn build_in_place_bonus(&self, choice: &[char], b: &mut [i32]) {
-        let mut prev_ch = '\0';
+    fn build_in_place_bonus<C: Copy + Into<char>>(&self, choice: &[C], b: &mut [i32]) {
+        let mut prev_ch_type = CharType::Empty;
         for (j, &c_ch) in choice.iter().enumerate() {
-            let prev_ch_type = CharType::of(prev_ch);
-            let ch_type = CharType::of(c_ch);
+            let ch_type = self.classifier.char_type(c_ch.into());
             b[j + 1] = self.in_place_bonus(prev_ch_type, ch_type);
-            prev_ch = c_ch;
+            prev_ch_type = ch_type;
         }
 
         if b.len() > 1 {
@@ -782,15 +914,15 @@ impl SkimMatcherV2 {
     /// Calculate the matching score of the characters
     /// return None if not matched.
     fn calculate_match_score(&self, c: char, p: char, case_sensitive: bool) -> Option<u16> {
-        if !char_equal(c, p, case_sensitive) {
+        if !char_equal(c, p, case_sensitive, self.case_folding) {
             return None;
         }
 
         let score = self.score_config.score_match;
         let mut bonus = 0;
 
-        // penalty on case mismatch
-        if !case_sensitive && p != c {
+        // penalty on case mismatch, a mistyped character costs the typo penalty only
+        if !case_sensitive && p != c && p != WILDCARD {
             bonus += self.score_config.penalty_case_mismatch;
         }
 
@@ -807,39 +939,148 @@ impl SkimMatcherV2 {
         }
     }
 
-    fn contains_upper(&self, string: &str) -> bool {
-        for ch in string.chars() {
-            if ch.is_ascii_uppercase() {
-                return true;
-            }
+    pub fn fuzzy(
+        &self,
+        choice: &str,
+        pattern: &str,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if is_ascii_units(choice, self.graphemes) && is_ascii_units(pattern, self.graphemes) {
+            // ASCII characters are neither normalized nor expanded, match on the bytes directly
+            return self.fuzzy_bytes(choice.as_bytes(), pattern.as_bytes(), with_pos);
         }
 
-        false
+        if pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        let case_sensitive = self.is_case_sensitive(pattern);
+
+        // the clusters are matched by their first characters, which may_match doesn't know
+        let result = if self.max_typos == 0
+            && !self.graphemes
+            && !may_match(
+                choice,
+                pattern,
+                case_sensitive,
+                self.case_folding,
+                self.normalize,
+            ) {
+            None
+        } else {
+            let mut choice_chars = self
+                .c_cache
+                .get_or(|| RefCell::new(Vec::new()))
+                .borrow_mut();
+            let mut pattern_chars = self
+                .p_cache
+                .get_or(|| RefCell::new(Vec::new()))
+                .borrow_mut();
+
+            choice_chars.clear();
+            extend_units(&mut choice_chars, choice, self.graphemes);
+            pattern_chars.clear();
+            extend_units(&mut pattern_chars, pattern, self.graphemes);
+
+            self.fuzzy_transformed(
+                &mut choice_chars,
+                &mut pattern_chars,
+                case_sensitive,
+                with_pos,
+            )
+        };
+
+        self.release_caches();
+        result
     }
 
-    pub fn fuzzy(
+    /// Same as `fuzzy`, but on the characters decoded already, so that the choices could be
+    /// decoded once and matched many times. Custom tokens could be matched by mapping them to
+    /// characters, e.g. the ones in the private use area.
+    pub fn fuzzy_chars(
         &self,
-        choice: &str,
-        pattern: &str,
+        choice: &[char],
+        pattern: &[char],
         with_pos: bool,
     ) -> Option<(ScoreType, Vec<IndexType>)> {
         if pattern.is_empty() {
             return Some((0, Vec::new()));
         }
 
-        let case_sensitive = match self.case {
-            CaseMatching::Respect => true,
-            CaseMatching::Ignore => false,
-            CaseMatching::Smart => self.contains_upper(pattern),
+        let case_sensitive = self.is_case_sensitive_atoms(pattern);
+        let result = if self.transforms(case_sensitive) {
+            self.fuzzy_copied(choice, pattern, case_sensitive, with_pos)
+        } else {
+            let first_match_indices =
+                cheap_matches(choice, pattern, case_sensitive, self.case_folding);
+            self.fuzzy_prefiltered(
+                choice,
+                pattern,
+                first_match_indices,
+                case_sensitive,
+                with_pos,
+            )
         };
 
-        let compressed = !with_pos;
+        self.release_caches();
+        result
+    }
 
-        // initialize the score matrix
-        let mut m = self
-            .m_cache
-            .get_or(|| RefCell::new(Vec::new()))
-            .borrow_mut();
+    /// Same as `fuzzy`, but every byte is a character, e.g. ASCII text. The bytes that are not
+    /// ASCII are treated as the characters of the same code points, i.e. Latin-1.
+    pub fn fuzzy_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        let case_sensitive = self.is_case_sensitive_atoms(pattern);
+        let result = if choice.is_ascii() && pattern.is_ascii() {
+            let first_match_indices = cheap_matches_ascii(choice, pattern, case_sensitive);
+            self.fuzzy_prefiltered(
+                choice,
+                pattern,
+                first_match_indices,
+                case_sensitive,
+                with_pos,
+            )
+        } else if self.transforms(case_sensitive) {
+            let choice: Vec<char> = choice.iter().map(|&b| b.into()).collect();
+            let pattern: Vec<char> = pattern.iter().map(|&b| b.into()).collect();
+            self.fuzzy_copied(&choice, &pattern, case_sensitive, with_pos)
+        } else {
+            let first_match_indices =
+                cheap_matches(choice, pattern, case_sensitive, self.case_folding);
+            self.fuzzy_prefiltered(
+                choice,
+                pattern,
+                first_match_indices,
+                case_sensitive,
+                with_pos,
+            )
+        };
+
+        self.release_caches();
+        result
+    }
+
+    /// Whether the characters would be transformed before matching, see `fuzzy_transformed`
+    fn transforms(&self, case_sensitive: bool) -> bool {
+        self.normalize || (!case_sensitive && self.case_folding == CaseFolding::Full)
+    }
+
+    /// Copy the characters into the caches to transform them, and match
+    fn fuzzy_copied(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
         let mut choice_chars = self
             .c_cache
             .get_or(|| RefCell::new(Vec::new()))
@@ -850,114 +1091,531 @@ impl SkimMatcherV2 {
             .borrow_mut();
 
         choice_chars.clear();
-        for char in choice.chars() {
-            choice_chars.push(char);
+        choice_chars.extend_from_slice(choice);
+        pattern_chars.clear();
+        pattern_chars.extend_from_slice(pattern);
+
+        self.fuzzy_transformed(
+            &mut choice_chars,
+            &mut pattern_chars,
+            case_sensitive,
+            with_pos,
+        )
+    }
+
+    /// Transform the characters in place and match them, the indices are mapped back to the
+    /// ones of the original choice.
+    fn fuzzy_transformed(
+        &self,
+        choice_chars: &mut Vec<char>,
+        pattern_chars: &mut Vec<char>,
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        // strip the diacritics if needed, and with full case folding, e.g. "ß" should match
+        // "ss", so expand these characters and match them one by one.
+        let expand_folding = !case_sensitive && self.case_folding == CaseFolding::Full;
+        transform_chars(pattern_chars, self.normalize, expand_folding);
+        // e.g. a pattern of combining marks only, which is stripped entirely
+        if pattern_chars.is_empty() {
+            return Some((0, Vec::new()));
         }
+        let original_indices = transform_chars(choice_chars, self.normalize, expand_folding);
 
-        pattern_chars.clear();
-        for char in pattern.chars() {
-            pattern_chars.push(char);
+        let first_match_indices = cheap_matches(
+            choice_chars,
+            pattern_chars,
+            case_sensitive,
+            self.case_folding,
+        );
+        let mut result = self.fuzzy_prefiltered(
+            choice_chars,
+            pattern_chars,
+            first_match_indices,
+            case_sensitive,
+            with_pos,
+        );
+        if let (Some((_, positions)), Some(original_indices)) =
+            (result.as_mut(), original_indices.as_ref())
+        {
+            map_original_indices(positions, original_indices);
         }
+        result
+    }
 
-        let first_match_indices = cheap_matches(&choice_chars, &pattern_chars, case_sensitive)?;
+    /// Match given the result of the prefilter, fall back to the typos if it doesn't match
+    fn fuzzy_prefiltered<C: Copy + Into<char>>(
+        &self,
+        choice: &[C],
+        pattern: &[C],
+        first_match_indices: Option<Vec<usize>>,
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        match first_match_indices {
+            Some(first_match_indices) => self.fuzzy_atoms(
+                choice,
+                pattern,
+                &first_match_indices,
+                case_sensitive,
+                with_pos,
+            ),
+            None => self.fuzzy_with_typos(choice, pattern, case_sensitive, with_pos),
+        }
+    }
 
-        let cols = choice_chars.len() + 1;
-        let num_char_pattern = pattern_chars.len();
-        let rows = if compressed { 2 } else { num_char_pattern + 1 };
+    fn release_caches(&self) {
+        if !self.use_cache {
+            // drop the allocated memory
+            self.m_cache.get().map(|cell| cell.replace(vec![]));
+            self.c_cache.get().map(|cell| cell.replace(vec![]));
+            self.p_cache.get().map(|cell| cell.replace(vec![]));
+        }
+    }
 
-        if self.element_limit > 0 && self.element_limit < rows * cols {
-            return self.simple_match(
-                &choice_chars,
-                &pattern_chars,
-                &first_match_indices,
+    /// Match the (transformed) characters, or the bytes if they are all ASCII, given the first
+    /// matched indices found by the prefilter. The returned indices are the ones of `choice`.
+    fn fuzzy_atoms<C: Copy + Into<char>>(
+        &self,
+        choice: &[C],
+        pattern: &[C],
+        first_match_indices: &[usize],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        if self.fzf_compat {
+            return Some(self.fuzzy_atoms_fzf(
+                choice,
+                pattern,
+                first_match_indices,
                 case_sensitive,
                 with_pos,
-            );
+            ))
+            .filter(|&(score, _)| self.reaches_min_score(score));
+        }
+
+        let compressed = !with_pos;
+
+        let cols = choice.len() + 1;
+        let num_char_pattern = pattern.len();
+        let rows = if compressed { 2 } else { num_char_pattern + 1 };
+
+        if self.element_limit > 0 && self.element_limit < rows * cols {
+            return self
+                .simple_match(
+                    choice,
+                    pattern,
+                    first_match_indices,
+                    case_sensitive,
+                    with_pos,
+                )
+                .filter(|&(score, _)| self.reaches_min_score(score));
         }
 
+        // initialize the score matrix
+        let mut m = self
+            .m_cache
+            .get_or(|| RefCell::new(Vec::new()))
+            .borrow_mut();
         let mut m = ScoreMatrix::new(&mut m, rows, cols);
-        self.build_score_matrix(
+        let completed = self.build_score_matrix(
             &mut m,
-            &choice_chars,
-            &pattern_chars,
-            &first_match_indices,
+            choice,
+            pattern,
+            first_match_indices,
             compressed,
             case_sensitive,
         );
+        if !completed {
+            return None;
+        }
+
+        let (pat_idx, m_score) =
+            self.best_of_last_row(&m, first_match_indices, num_char_pattern, compressed);
+        if !self.reaches_min_score(m_score as ScoreType) {
+            return None;
+        }
+
+        let mut positions = Vec::new();
+        if with_pos {
+            positions = self.backtrack(&m, pat_idx, first_match_indices[0]);
+        }
+
+        if self.debug {
+            println!("Matrix:\n{:?}", m);
+        }
+
+        Some((m_score as ScoreType, positions))
+    }
+
+    fn fuzzy_with_typos<C: Copy + Into<char>>(
+        &self,
+        choice: &[C],
+        pattern: &[C],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        // the wildcards of the variants are characters
+        let choice: Vec<char> = choice.iter().map(|&c| c.into()).collect();
+        let pattern: Vec<char> = pattern.iter().map(|&p| p.into()).collect();
+        self.match_typo_variants(&choice, &pattern, case_sensitive, with_pos)
+            .map(|typo_match| (typo_match.score, typo_match.indices))
+    }
+
+    /// Match the variants of the pattern with 1, 2, ... up to `max_typos` typos, and return the
+    /// best match among the ones with the fewest typos. More than half of the pattern should
+    /// match as is, otherwise a short pattern would match almost anything.
+    fn match_typo_variants(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> Option<TypoMatch> {
+        let max_typos = min(self.max_typos, pattern.len().saturating_sub(1) / 2);
+
+        // the variants to expand, with the end of the typos that may still make them match
+        let end = matched_prefix_len(choice, pattern, case_sensitive, self.case_folding);
+        let mut variants = vec![(pattern.to_vec(), end)];
+        let mut seen = HashSet::new();
+        seen.insert(pattern.to_vec());
+        for typos in 1..=max_typos {
+            let penalty = typos as ScoreType * self.typo_penalty;
+            let mut matches = Vec::new();
+            let mut next = Vec::new();
+            for (variant, end) in variants.iter() {
+                for variant in typo_variants(variant, *end) {
+                    if !seen.insert(variant.clone()) {
+                        continue;
+                    }
+                    let matched =
+                        cheap_matches(choice, &variant, case_sensitive, self.case_folding)
+                            .and_then(|first_match_indices| {
+                                self.fuzzy_atoms(
+                                    choice,
+                                    &variant,
+                                    &first_match_indices,
+                                    case_sensitive,
+                                    with_pos,
+                                )
+                            })
+                            .map(|(score, indices)| (score - penalty, indices))
+                            .filter(|&(score, _)| self.reaches_min_score(score));
+                    match matched {
+                        Some((score, indices)) => matches.push(TypoMatch {
+                            variant,
+                            typos,
+                            score,
+                            indices,
+                        }),
+                        None if matches.is_empty() && typos < max_typos => {
+                            let end = matched_prefix_len(
+                                choice,
+                                &variant,
+                                case_sensitive,
+                                self.case_folding,
+                            );
+                            next.push((variant, end));
+                        }
+                        None => {}
+                    }
+                }
+            }
+            if !matches.is_empty() {
+                return matches
+                    .into_iter()
+                    .max_by_key(|typo_match| typo_match.score);
+            }
+            variants = next;
+        }
+        None
+    }
+
+    fn fuzzy_atoms_fzf<C: Copy + Into<char>>(
+        &self,
+        choice: &[C],
+        pattern: &[C],
+        first_match_indices: &[usize],
+        case_sensitive: bool,
+        with_pos: bool,
+    ) -> (ScoreType, Vec<IndexType>) {
+        let algorithm =
+            if self.element_limit > 0 && self.element_limit < choice.len() * pattern.len() {
+                fzf::fuzzy_match_v1
+            } else {
+                fzf::fuzzy_match_v2
+            };
+        algorithm(
+            choice,
+            pattern,
+            first_match_indices,
+            case_sensitive,
+            self.case_folding,
+            with_pos,
+        )
+    }
+
+    fn reaches_min_score(&self, score: ScoreType) -> bool {
+        match self.min_score {
+            Some(min_score) => score >= min_score,
+            None => true,
+        }
+    }
+
+    fn is_case_sensitive(&self, pattern: &str) -> bool {
+        match self.case {
+            CaseMatching::Respect => true,
+            CaseMatching::Ignore => false,
+            CaseMatching::Smart => contains_upper(pattern, self.case_folding),
+        }
+    }
+
+    fn is_case_sensitive_atoms<C: Copy + Into<char>>(&self, pattern: &[C]) -> bool {
+        match self.case {
+            CaseMatching::Respect => true,
+            CaseMatching::Ignore => false,
+            CaseMatching::Smart => contains_upper_atoms(pattern, self.case_folding),
+        }
+    }
+
+    /// Find the best cell of the last row, return its column and score
+    fn best_of_last_row(
+        &self,
+        m: &ScoreMatrix,
+        first_match_indices: &[usize],
+        num_char_pattern: usize,
+        compressed: bool,
+    ) -> (usize, i32) {
         let first_col_of_last_row = first_match_indices[first_match_indices.len() - 1];
         let last_row = m.get_row(self.adjust_row_idx(num_char_pattern, compressed));
-        let (pat_idx, &MatrixCell { m_score, .. }) = last_row[first_col_of_last_row..]
+        last_row[first_col_of_last_row..]
             .iter()
             .enumerate()
             .max_by_key(|&(_, x)| x.m_score)
-            .map(|(idx, cell)| (idx + first_col_of_last_row, cell))
-            .expect("fuzzy_matcher failed to iterate over last_row");
+            .map(|(idx, cell)| (idx + first_col_of_last_row, cell.m_score))
+            .expect("fuzzy_matcher failed to iterate over last_row")
+    }
 
-        let mut positions = if with_pos {
-            Vec::with_capacity(num_char_pattern)
-        } else {
-            Vec::new()
+    /// Track the moves back from the cell `(last row, col)` for the matched indices, the matrix
+    /// should not be compressed.
+    fn backtrack(&self, m: &ScoreMatrix, col: usize, first_col_first_row: usize) -> Vec<IndexType> {
+        let mut positions = Vec::with_capacity(m.rows - 1);
+        let mut i = m.rows - 1;
+        let mut j = col;
+        let mut track_m = true;
+        let mut current_move = Match;
+        while i > 0 && j > first_col_first_row {
+            if current_move == Match {
+                positions.push((j - 1) as IndexType);
+            }
+
+            let cell = &m[(i, j)];
+            current_move = if track_m { cell.m_move } else { cell.p_move };
+            if track_m {
+                i -= 1;
+            }
+
+            j -= 1;
+
+            track_m = match current_move {
+                Match => true,
+                Skip => false,
+            };
+        }
+        positions.reverse();
+        positions
+    }
+
+    /// Explain how the score of matching `choice` with `pattern` is calculated, `min_score` is
+    /// respected. With typos the variant of the pattern that matches is explained. Only the scoring of the score matrix can be explained, None is returned when
+    /// `element_limit` makes the matching fall back to `simple_match`, and always with
+    /// `fzf_compat`.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn explain(&self, choice: &str, pattern: &str) -> Option<Explanation> {
+        if self.fzf_compat {
+            return None;
+        }
+
+        let case_sensitive = self.is_case_sensitive(pattern);
+
+        let mut choice_chars = Vec::new();
+        let mut pattern_chars = Vec::new();
+        extend_units(&mut choice_chars, choice, self.graphemes);
+        extend_units(&mut pattern_chars, pattern, self.graphemes);
+        let expand_folding = !case_sensitive && self.case_folding == CaseFolding::Full;
+        transform_chars(&mut pattern_chars, self.normalize, expand_folding);
+        let original_indices = transform_chars(&mut choice_chars, self.normalize, expand_folding);
+
+        if pattern_chars.is_empty() {
+            return Some(Explanation::default());
+        }
+
+        // explain the variant of the pattern that matches with the fewest typos
+        let (pattern_chars, typos) = match cheap_matches(
+            &choice_chars,
+            &pattern_chars,
+            case_sensitive,
+            self.case_folding,
+        ) {
+            Some(_) => (pattern_chars, 0),
+            None => {
+                let typo_match =
+                    self.match_typo_variants(&choice_chars, &pattern_chars, case_sensitive, true)?;
+                (typo_match.variant, typo_match.typos)
+            }
         };
-        if with_pos {
-            let mut i = m.rows - 1;
-            let mut j = pat_idx;
-            let mut track_m = true;
-            let mut current_move = Match;
-            let first_col_first_row = first_match_indices[0];
-            while i > 0 && j > first_col_first_row {
-                if current_move == Match {
-                    positions.push((j - 1) as IndexType);
-                }
 
-                let cell = &m[(i, j)];
-                current_move = if track_m { cell.m_move } else { cell.p_move };
-                if track_m {
-                    i -= 1;
+        let rows = pattern_chars.len() + 1;
+        let cols = choice_chars.len() + 1;
+        if self.element_limit > 0 && self.element_limit < rows * cols {
+            return None;
+        }
+
+        let first_match_indices = cheap_matches(
+            &choice_chars,
+            &pattern_chars,
+            case_sensitive,
+            self.case_folding,
+        )?;
+
+        let mut matrix = Vec::new();
+        let mut m = ScoreMatrix::new(&mut matrix, rows, cols);
+        let completed = self.build_score_matrix(
+            &mut m,
+            &choice_chars,
+            &pattern_chars,
+            &first_match_indices,
+            false,
+            case_sensitive,
+        );
+        if !completed {
+            return None;
+        }
+
+        let (last_col, score) =
+            self.best_of_last_row(&m, &first_match_indices, pattern_chars.len(), false);
+        let typo_penalty = typos as ScoreType * self.typo_penalty;
+        let score = score as ScoreType - typo_penalty;
+        if !self.reaches_min_score(score) {
+            return None;
+        }
+        let positions = self.backtrack(&m, last_col, first_match_indices[0]);
+
+        let mut in_place_bonuses = vec![0; m.cols];
+        self.build_in_place_bonus(&choice_chars, &mut in_place_bonuses);
+
+        let mut matches = Vec::with_capacity(positions.len());
+        let mut prev_col = None;
+        for (i, &pos) in positions.iter().enumerate() {
+            let col = pos as usize + 1;
+            let c = choice_chars[col - 1];
+            let match_score = self
+                .calculate_match_score(c, pattern_chars[i], case_sensitive)
+                .expect("matched characters should be equal") as i32;
+
+            let mut contributions = vec![];
+            let mut add = |kind, score: i32| {
+                if score != 0 {
+                    contributions.push(Contribution {
+                        kind,
+                        score: score as ScoreType,
+                    });
                 }
+            };
 
-                j -= 1;
+            add(ScoreKind::Match, self.score_config.score_match);
+            add(
+                ScoreKind::CaseMismatch,
+                match_score - self.score_config.score_match,
+            );
 
-                track_m = match current_move {
-                    Match => true,
-                    Skip => false,
-                };
+            match prev_col {
+                Some(prev_col) if prev_col + 1 == col => {
+                    // the bonus of consecutive matches carries the bonus of the former ones,
+                    // take the actual one from the matrix
+                    let consecutive_bonus =
+                        m[(i + 1, col)].m_score - m[(i, prev_col)].m_score - match_score;
+                    add(ScoreKind::Consecutive, consecutive_bonus);
+                }
+                _ => {
+                    let prev_type = if col > 1 {
+                        self.classifier.char_type(choice_chars[col - 2])
+                    } else {
+                        CharType::Empty
+                    };
+                    let kind = match CharRole::of_type(prev_type, self.classifier.char_type(c)) {
+                        CharRole::Head | CharRole::Tail => ScoreKind::Head,
+                        CharRole::Camel => ScoreKind::Camel,
+                        CharRole::Break => ScoreKind::Break,
+                    };
+                    add(kind, in_place_bonuses[col]);
+
+                    match prev_col {
+                        // the characters before the first match are penalized only once
+                        None => add(ScoreKind::GapExtension, self.score_config.gap_extension),
+                        Some(prev_col) => {
+                            add(ScoreKind::GapStart, self.score_config.gap_start);
+                            let gap = (col - prev_col - 1) as i32;
+                            add(
+                                ScoreKind::GapExtension,
+                                gap * self.score_config.gap_extension,
+                            );
+                        }
+                    }
+                }
             }
-            positions.reverse();
+
+            let index = match original_indices.as_ref() {
+                Some(original_indices) => original_indices[col - 1],
+                None => col - 1,
+            };
+            matches.push(MatchedChar {
+                index: index as IndexType,
+                ch: c,
+                contributions,
+            });
+            prev_col = Some(col);
         }
 
-        if self.debug {
-            println!("Matrix:\n{:?}", m);
+        let mut indices = positions;
+        if let Some(original_indices) = original_indices.as_ref() {
+            map_original_indices(&mut indices, original_indices);
         }
 
-        if !self.use_cache {
-            // drop the allocated memory
-            self.m_cache.get().map(|cell| cell.replace(vec![]));
-            self.c_cache.get().map(|cell| cell.replace(vec![]));
-            self.p_cache.get().map(|cell| cell.replace(vec![]));
+        let mut adjustments = Vec::new();
+        if typo_penalty != 0 {
+            adjustments.push(Contribution {
+                kind: ScoreKind::Typo,
+                score: -typo_penalty,
+            });
         }
 
-        Some((m_score as ScoreType, positions))
+        Some(Explanation {
+            score,
+            indices,
+            matches,
+            adjustments,
+        })
     }
 
-    pub fn simple_match(
+    pub fn simple_match<C: Copy + Into<char>>(
         &self,
-        choice: &[char],
-        pattern: &[char],
+        choice: &[C],
+        pattern: &[C],
         first_match_indices: &[usize],
         case_sensitive: bool,
         with_pos: bool,
     ) -> Option<(ScoreType, Vec<IndexType>)> {
-        if pattern.len() <= 0 {
+        if pattern.is_empty() {
             return Some((0, Vec::new()));
         } else if pattern.len() == 1 {
             let match_idx = first_match_indices[0];
-            let prev_ch = if match_idx > 0 {
-                choice[match_idx - 1]
+            let prev_ch_type = if match_idx > 0 {
+                self.classifier.char_type(choice[match_idx - 1].into())
             } else {
-                '\0'
+                CharType::Empty
             };
-            let prev_ch_type = CharType::of(prev_ch);
-            let ch_type = CharType::of(choice[match_idx]);
+            let ch_type = self.classifier.char_type(choice[match_idx].into());
             let in_place_bonus = self.in_place_bonus(prev_ch_type, ch_type);
             return Some((in_place_bonus as ScoreType, vec![match_idx as IndexType]));
         }
@@ -969,7 +1627,7 @@ impl SkimMatcherV2 {
         for (idx, &c) in choice[start_idx..=end_idx].iter().enumerate().rev() {
             match pattern_iter.peek() {
                 Some(&&p) => {
-                    if char_equal(c, p, case_sensitive) {
+                    if char_equal(c.into(), p.into(), case_sensitive, self.case_folding) {
                         let _ = pattern_iter.next();
                         start_idx = idx;
                     }
@@ -988,10 +1646,10 @@ impl SkimMatcherV2 {
         ))
     }
 
-    fn calculate_score_with_pos(
+    fn calculate_score_with_pos<C: Copy + Into<char>>(
         &self,
-        choice: &[char],
-        pattern: &[char],
+        choice: &[C],
+        pattern: &[C],
         start_idx: usize,
         end_idx: usize,
         case_sensitive: bool,
@@ -1004,23 +1662,24 @@ impl SkimMatcherV2 {
 
         // unfortunately we could not get the the character before the first character's(for performance)
         // so we tread them as NonWord
-        let mut prev_ch = '\0';
+        let mut prev_ch_type = CharType::Empty;
 
         let mut score: i32 = 0;
         let mut in_gap = false;
         let mut prev_match_bonus = 0;
 
         for (c_idx, &c) in choice_iter {
+            let c: char = c.into();
             let op = pattern_iter.peek();
             if op.is_none() {
                 break;
             }
 
-            let prev_ch_type = CharType::of(prev_ch);
-            let ch_type = CharType::of(c);
+            let ch_type = self.classifier.char_type(c);
             let in_place_bonus = self.in_place_bonus(prev_ch_type, ch_type);
 
             let (_p_idx, &p) = *op.unwrap();
+            let p: char = p.into();
 
             if let Some(match_score) = self.calculate_match_score(c, p, case_sensitive) {
                 if with_pos {
@@ -1051,7 +1710,7 @@ impl SkimMatcherV2 {
                 prev_match_bonus = 0;
             }
 
-            prev_ch = c;
+            prev_ch_type = ch_type;
         }
 
         (score as ScoreType, pos)
@@ -1066,6 +1725,59 @@ impl FuzzyMatcher for SkimMatcherV2 {
     fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<ScoreType> {
         self.fuzzy(choice, pattern, false).map(|(score, _)| score)
     }
+
+    /// Every unit of the pattern scores at most `max_score_per_char`, or as in fzf with
+    /// `fzf_compat`. None if the gaps are rewarded, as the score then grows with the choice.
+    #[allow(clippy::unnecessary_cast)]
+    fn max_score(&self, pattern: &str) -> Option<ScoreType> {
+        let expand_folding =
+            !self.is_case_sensitive(pattern) && self.case_folding == CaseFolding::Full;
+        let num_pattern = count_units(pattern, self.graphemes, self.normalize, expand_folding);
+        let max_score = if self.fzf_compat {
+            fzf::max_score(num_pattern)
+        } else {
+            num_pattern as ScoreType * self.max_score_per_char()?
+        };
+
+        // a typo variant is shorter or as long as the pattern, but a negative penalty rewards it
+        Some(max_score + self.max_typos as ScoreType * max(0, -self.typo_penalty))
+    }
+
+    /// A longer pattern may reach `min_score` when the shorter one doesn't, and a typo variant
+    /// of it may match when the shorter one has none, e.g. "abc" with a typo matches "xbc".
+    fn narrows(&self) -> bool {
+        self.min_score.is_none() && self.max_typos == 0
+    }
+
+    fn indexes_graphemes(&self) -> bool {
+        self.graphemes
+    }
+
+    fn fuzzy_indices_chars(
+        &self,
+        choice: &[char],
+        pattern: &[char],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy_chars(choice, pattern, true)
+    }
+
+    fn fuzzy_match_chars(&self, choice: &[char], pattern: &[char]) -> Option<ScoreType> {
+        self.fuzzy_chars(choice, pattern, false)
+            .map(|(score, _)| score)
+    }
+
+    fn fuzzy_indices_bytes(
+        &self,
+        choice: &[u8],
+        pattern: &[u8],
+    ) -> Option<(ScoreType, Vec<IndexType>)> {
+        self.fuzzy_bytes(choice, pattern, true)
+    }
+
+    fn fuzzy_match_bytes(&self, choice: &[u8], pattern: &[u8]) -> Option<ScoreType> {
+        self.fuzzy_bytes(choice, pattern, false)
+            .map(|(score, _)| score)
+    }
 }
 
 #[cfg(test)]
@@ -1149,7 +1861,8 @@ mod tests {
     ) -> Option<(ScoreType, Vec<IndexType>)> {
         let choice: Vec<char> = choice.chars().collect();
         let pattern: Vec<char> = pattern.chars().collect();
-        let first_match_indices = cheap_matches(&choice, &pattern, case_sensitive)?;
+        let first_match_indices =
+            cheap_matches(&choice, &pattern, case_sensitive, matcher.case_folding)?;
         matcher.simple_match(
             &choice,
             &pattern,
@@ -1247,6 +1960,417 @@ mod tests {
         assert!(matcher.fuzzy_match("aBc", "aBC").is_none());
     }
 
+    #[test]
+    fn test_case_folding_v2() {
+        let matcher = SkimMatcherV2::default();
+        assert!(matcher.fuzzy_match("ÄBC", "äbc").is_none());
+        assert!(matcher.fuzzy_match("straße", "strasse").is_none());
+
+        let matcher = SkimMatcherV2::default().case_folding(CaseFolding::Simple);
+        assert!(matcher.fuzzy_match("ÄBC", "äbc").is_some());
+        assert!(matcher.fuzzy_match("ΣΟΦΟΣ", "σοφος").is_some());
+        assert!(matcher.fuzzy_match("Привет", "привет").is_some());
+        assert!(matcher.fuzzy_match("straße", "strasse").is_none());
+
+        // smart case should consider unicode upper case
+        assert!(matcher.fuzzy_match("äbc", "Äbc").is_none());
+        assert!(matcher.fuzzy_match("Äbc", "Äbc").is_some());
+
+        let matcher = SkimMatcherV2::default().case_folding(CaseFolding::Full);
+        assert!(matcher.fuzzy_match("straße", "strasse").is_some());
+        assert!(matcher.fuzzy_match("STRASSE", "straße").is_some());
+        assert!(matcher.fuzzy_match("ﬁle", "file").is_some());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "Straße", "sse").unwrap(),
+            "[S]tra[ß][e]"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "Maßstab", "masst").unwrap(),
+            "[M][a][ß][s][t]ab"
+        );
+    }
+
+    #[test]
+    fn test_normalize_v2() {
+        let matcher = SkimMatcherV2::default();
+        assert!(matcher.fuzzy_match("résumé", "resume").is_none());
+
+        let matcher = SkimMatcherV2::default().normalize(true);
+        assert!(matcher.fuzzy_match("résumé", "resume").is_some());
+        assert!(matcher.fuzzy_match("Zürich", "zurich").is_some());
+        assert!(matcher.fuzzy_match("resume", "résumé").is_some());
+        assert!(matcher.fuzzy_match("Łódź", "lodz").is_some());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "résumé", "rsm").unwrap(),
+            "[r]é[s]u[m]é"
+        );
+
+        // nothing is left of a pattern of combining marks
+        assert_eq!(matcher.fuzzy_match("résumé", "\u{301}"), Some(0));
+        assert_eq!(
+            matcher.fuzzy_indices("résumé", "\u{301}\u{308}"),
+            Some((0, Vec::new()))
+        );
+        let chars: Vec<char> = "résumé".chars().collect();
+        assert_eq!(matcher.fuzzy_match_chars(&chars, &['\u{301}']), Some(0));
+
+        // decomposed characters, indices should refer to the original choice
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "re\u{301}sume\u{301}", "esm").unwrap(),
+            "r[e]\u{301}[s]u[m]e\u{301}"
+        );
+
+        let matcher = SkimMatcherV2::default()
+            .normalize(true)
+            .case_folding(CaseFolding::Full);
+        assert!(matcher.fuzzy_match("Straße", "strasse").is_some());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "Gru\u{308}ße", "grusse").unwrap(),
+            "[G][r][u]\u{308}[ß][e]"
+        );
+    }
+
+    #[test]
+    fn test_char_classifier_v2() {
+        #[derive(Debug)]
+        struct KebabClassifier;
+        impl CharClassifier for KebabClassifier {
+            fn char_type(&self, ch: char) -> CharType {
+                match ch {
+                    '-' => CharType::HardSep,
+                    _ => SkimClassifier.char_type(ch),
+                }
+            }
+        }
+
+        let matcher = SkimMatcherV2::default();
+        assert!(matcher.fuzzy_match("foo-bar", "bar") < matcher.fuzzy_match("foo/bar", "bar"));
+
+        let matcher = SkimMatcherV2::default().char_classifier(KebabClassifier);
+        assert_eq!(
+            matcher.fuzzy_match("foo-bar", "bar"),
+            matcher.fuzzy_match("foo/bar", "bar")
+        );
+        assert_eq!(
+            matcher.fuzzy_match("foo-bar", "b"),
+            matcher.fuzzy_match("foo/bar", "b")
+        );
+    }
+
+    #[test]
+    fn test_explain_v2() {
+        let matcher = SkimMatcherV2::default();
+        let cases = [
+            ("axbycz", "abc"),
+            ("foo_bar", "fb"),
+            ("FooBarBaz", "fbb"),
+            ("src/fuzzy_matcher/skim.rs", "skim"),
+            ("aaaaab", "aab"),
+            ("Hello, 世界", "H世界"),
+        ];
+        for &(choice, pattern) in cases.iter() {
+            let explanation = matcher.explain(choice, pattern).unwrap();
+            let (score, indices) = matcher.fuzzy_indices(choice, pattern).unwrap();
+            assert_eq!(explanation.score, score, "{} / {}", choice, pattern);
+            assert_eq!(explanation.total(), score, "{}", explanation);
+            assert_eq!(explanation.indices, indices);
+        }
+        assert!(matcher.explain("abc", "abx").is_none());
+
+        // the simple match is not explained
+        let matcher = SkimMatcherV2::default().element_limit(10);
+        assert!(matcher.fuzzy_match("foo_bar_baz", "fbb").is_some());
+        assert!(matcher.explain("foo_bar_baz", "fbb").is_none());
+        let matcher = matcher.element_limit(48);
+        let explanation = matcher.explain("foo_bar_baz", "fbb").unwrap();
+        assert_eq!(
+            Some(explanation.score),
+            matcher.fuzzy_match("foo_bar_baz", "fbb")
+        );
+
+        let explanation = matcher.explain("fooBar", "fB").unwrap();
+        let kinds: Vec<Vec<ScoreKind>> = explanation
+            .matches
+            .iter()
+            .map(|matched| matched.contributions.iter().map(|c| c.kind).collect())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                vec![ScoreKind::Match, ScoreKind::Head, ScoreKind::GapExtension],
+                vec![
+                    ScoreKind::Match,
+                    ScoreKind::Camel,
+                    ScoreKind::GapStart,
+                    ScoreKind::GapExtension
+                ],
+            ]
+        );
+
+        // the variant matched with typos, which costs the typo penalty
+        let matcher = SkimMatcherV2::default().max_typos(1);
+        for &pattern in ["fuzyz", "fuzzt", "xfuzzy"].iter() {
+            let explanation = matcher.explain("fuzzy", pattern).unwrap();
+            let (score, indices) = matcher.fuzzy_indices("fuzzy", pattern).unwrap();
+            assert_eq!(explanation.score, score, "{}", pattern);
+            assert_eq!(explanation.total(), score, "{}", explanation);
+            assert_eq!(explanation.indices, indices);
+            assert_eq!(
+                explanation.adjustments,
+                [Contribution {
+                    kind: ScoreKind::Typo,
+                    score: -32,
+                }]
+            );
+        }
+        assert!(matcher.explain("fuzzy", "fxyzz").is_none());
+
+        let matcher = SkimMatcherV2::default().normalize(true);
+        let explanation = matcher.explain("résumé", "rsm").unwrap();
+        assert_eq!(explanation.total(), explanation.score);
+        assert_eq!(explanation.matches[2].index, 4);
+    }
+
+    #[test]
+    fn test_min_score_v2() {
+        let choices = [
+            "abc",
+            "axbycz",
+            "a_b_c",
+            "AbCd",
+            "xxxxxxxxxxxxxxxxxxxxaxxxxxxxxxxxxbxxxxxxxxxxxxxxxxc",
+            "src/fuzzy_matcher/skim.rs",
+            "fuzzy_matcher/src/lib.rs",
+        ];
+        let patterns = ["a", "abc", "ac", "fm", "skim", "src"];
+
+        let matcher = SkimMatcherV2::default();
+        let max_score_per_char = matcher.max_score_per_char().unwrap();
+        for &pattern in patterns.iter() {
+            for &choice in choices.iter() {
+                let score = matcher.fuzzy_match(choice, pattern);
+                if let Some(score) = score {
+                    // the bound used for pruning should hold
+                    let num_chars = pattern.chars().count() as ScoreType;
+                    assert!(
+                        score
+                            <= ScoreType::from(matcher.score_config.gap_extension)
+                                + num_chars * max_score_per_char
+                    );
+                }
+
+                for &min_score in [-100, 0, 20, 40, 60, 100].iter() {
+                    let pruned = SkimMatcherV2::default().min_score(min_score);
+                    let expected = score.filter(|&score| score >= min_score);
+                    assert_eq!(pruned.fuzzy_match(choice, pattern), expected);
+                    assert_eq!(
+                        pruned.fuzzy_indices(choice, pattern),
+                        matcher
+                            .fuzzy_indices(choice, pattern)
+                            .filter(|&(score, _)| score >= min_score)
+                    );
+                }
+            }
+        }
+
+        let matcher = SkimMatcherV2::default().min_score(1000);
+        assert_eq!(matcher.fuzzy_match("abc", ""), Some(0));
+        assert!(matcher.explain("abc", "abc").is_none());
+    }
+
+    #[test]
+    fn test_ascii_same_as_chars_v2() {
+        let choices = [
+            "axbycz",
+            "fooBarBaz",
+            "src/skim.rs",
+            "a_b-c d",
+            "AbCABC",
+            "abc",
+        ];
+        let patterns = ["abc", "fbb", "sr", "ABC", "b-c", "a"];
+        let matchers = [
+            SkimMatcherV2::default(),
+            SkimMatcherV2::default().ignore_case(),
+            SkimMatcherV2::default().respect_case(),
+            SkimMatcherV2::default().element_limit(10),
+            SkimMatcherV2::default().min_score(30),
+        ];
+        for matcher in matchers.iter() {
+            for choice in choices.iter() {
+                for pattern in patterns.iter() {
+                    let case_sensitive = matcher.is_case_sensitive(pattern);
+                    let choice_chars: Vec<char> = choice.chars().collect();
+                    let pattern_chars: Vec<char> = pattern.chars().collect();
+                    let first_match_indices = cheap_matches(
+                        &choice_chars,
+                        &pattern_chars,
+                        case_sensitive,
+                        matcher.case_folding,
+                    );
+                    assert_eq!(
+                        cheap_matches_ascii(choice.as_bytes(), pattern.as_bytes(), case_sensitive),
+                        first_match_indices
+                    );
+
+                    let first_match_indices = match first_match_indices {
+                        Some(indices) => indices,
+                        None => continue,
+                    };
+                    for &with_pos in [true, false].iter() {
+                        assert_eq!(
+                            matcher.fuzzy_atoms(
+                                choice.as_bytes(),
+                                pattern.as_bytes(),
+                                &first_match_indices,
+                                case_sensitive,
+                                with_pos
+                            ),
+                            matcher.fuzzy_atoms(
+                                &choice_chars,
+                                &pattern_chars,
+                                &first_match_indices,
+                                case_sensitive,
+                                with_pos
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefilter_v2() {
+        let matcher = SkimMatcherV2::default();
+        assert!(matcher.fuzzy_match("a世b界c", "abc").is_some());
+        assert!(matcher.fuzzy_match("A世B界C", "abc").is_some());
+        assert!(matcher.fuzzy_match("a世b界c", "aBc").is_none());
+        assert!(matcher.fuzzy_match("a世b界", "abc").is_none());
+
+        // non-ASCII characters could still match ASCII ones
+        let matcher = SkimMatcherV2::default().case_folding(CaseFolding::Simple);
+        assert!(matcher.fuzzy_match("\u{212A}elvin", "kelvin").is_some());
+        let matcher = SkimMatcherV2::default().normalize(true);
+        assert!(matcher.fuzzy_match("café", "cafe").is_some());
+        let matcher = SkimMatcherV2::default().case_folding(CaseFolding::Full);
+        assert!(matcher.fuzzy_match("Straße", "strasse").is_some());
+    }
+
+    #[test]
+    fn test_no_cache_v2() {
+        let matcher = SkimMatcherV2::default().use_cache(false);
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "axbycz", "abc").unwrap(),
+            "[a]x[b]y[c]z"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "a世b界c", "abc").unwrap(),
+            "[a]世[b]界[c]"
+        );
+        assert!(matcher.fuzzy_match("a世b界c", "abx").is_none());
+    }
+
+    #[test]
+    fn test_typos_v2() {
+        let exact = SkimMatcherV2::default();
+        let matcher = SkimMatcherV2::default().max_typos(1);
+        assert!(exact.fuzzy_match("fuzzy", "fuzyz").is_none());
+
+        // no penalty without typos
+        assert_eq!(
+            matcher.fuzzy_match("fuzzy", "fuzzy"),
+            exact.fuzzy_match("fuzzy", "fuzzy")
+        );
+
+        // swapped
+        let exact_score = exact.fuzzy_match("fuzzy", "fuzzy").unwrap();
+        assert_eq!(
+            matcher.fuzzy_indices("fuzzy", "fuzyz"),
+            Some((exact_score - 32, vec![0, 1, 2, 3, 4]))
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "世界你好", "世你界好").unwrap(),
+            "[世][界][你][好]"
+        );
+
+        // mistyped or extra
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "fuzzy", "fuzzt").unwrap(),
+            "[f][u][z][z][y]"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "foo bar", "fxo").unwrap(),
+            "[f][o][o] bar"
+        );
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "fuzzy", "xfuzzy").unwrap(),
+            "[f][u][z][z][y]"
+        );
+
+        // bounded
+        assert!(matcher.fuzzy_match("fuzzy", "fuzyzx").is_none());
+        let matcher = matcher.max_typos(2).typo_penalty(10);
+        assert_eq!(
+            matcher.fuzzy_indices("fuzzy", "fuzyzx"),
+            Some((exact_score - 20, vec![0, 1, 2, 3, 4]))
+        );
+        let matcher = matcher.min_score(exact_score - 19);
+        assert!(matcher.fuzzy_match("fuzzy", "fuzyzx").is_none());
+
+        // most of the pattern has to match as is
+        let matcher = SkimMatcherV2::default().max_typos(1);
+        assert!(matcher.fuzzy_match("a", "x").is_none());
+        assert!(matcher.fuzzy_match("abc", "x").is_none());
+        assert_eq!(matcher.fuzzy_match("x", "x"), exact.fuzzy_match("x", "x"));
+        assert!(matcher.fuzzy_match("ab", "xb").is_none());
+        assert!(matcher.fuzzy_match("hello world", "hx").is_none());
+        assert_eq!(
+            wrap_fuzzy_match(&matcher, "abc", "xbc").unwrap(),
+            "[a][b][c]"
+        );
+        assert!(matcher.max_typos(2).fuzzy_match("a", "xya").is_none());
+
+        // at most 3 typos
+        let matcher = SkimMatcherV2::default().max_typos(10);
+        assert!(matcher.fuzzy_match("abcdefghij", "axxxefghij").is_some());
+        assert!(matcher.fuzzy_match("abcdefghij", "axxxxfghij").is_none());
+    }
+
+    #[test]
+    fn test_graphemes_v2() {
+        let matcher = SkimMatcherV2::default().graphemes(true);
+        let (_, indices) = matcher.fuzzy_indices("cafe\u{301} bar", "eb").unwrap();
+        assert_eq!(indices, [3, 5]);
+        let result = matcher.fuzzy_result("cafe\u{301} bar", "eb").unwrap();
+        assert_eq!(result.ranges, [3..6, 7..8]);
+
+        let result = matcher.fuzzy_result("👍🏽 ok", "👍o").unwrap();
+        assert_eq!(result.indices, [0, 2]);
+        assert_eq!(result.ranges, [0..8, 9..10]);
+
+        // a flag is a pair of regional indicators
+        let flags = "🇫🇷🇩🇪";
+        let (_, indices) = matcher.fuzzy_indices(flags, "🇩").unwrap();
+        assert_eq!(indices, [1]);
+        let (_, indices) = SkimMatcherV2::default().fuzzy_indices(flags, "🇩").unwrap();
+        assert_eq!(indices, [2]);
+
+        // "\r\n" is a single cluster
+        let (_, indices) = matcher.fuzzy_indices("a\r\nb", "ab").unwrap();
+        assert_eq!(indices, [0, 2]);
+
+        // a cluster is matched by its first character
+        assert!(matcher.fuzzy_match("e\u{301}", "e").is_some());
+        assert!(matcher.fuzzy_match("e\u{301}", "\u{301}").is_none());
+        let matcher = matcher.normalize(true);
+        assert!(matcher.fuzzy_match("résumé", "re\u{301}sume").is_some());
+        assert_eq!(
+            matcher.explain("cafe\u{301} bar", "eb").unwrap().indices,
+            [3, 5]
+        );
+    }
+
     #[test]
     fn test_matcher_quality_v2() {
         let matcher = SkimMatcherV2::default();
@@ -1273,7 +2397,7 @@ mod tests {
         assert_order(&matcher, "ast", &["ast", "AST", "INT_FAST16_MAX"]);
         assert_order(&matcher, "int", &["int", "INT", "PRINT"]);
     }
-    
+
     #[test]
     fn test_reuse_should_not_affect_indices() {
         let matcher = SkimMatcherV2::default();
@@ -1,16 +1,24 @@
-use crate::{FuzzyMatcher, IndexType, ScoreType};
+use std::cmp::min;
 
-pub fn cheap_matches(
-    choice: &[char],
-    pattern: &[char],
+use memchr::{memchr, memchr2};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::classifier::CharType;
+use crate::highlight::segments;
+use crate::{CaseFolding, FuzzyMatcher, IndexType, ScoreType};
+
+pub fn cheap_matches<C: Copy + Into<char>>(
+    choice: &[C],
+    pattern: &[C],
     case_sensitive: bool,
+    case_folding: CaseFolding,
 ) -> Option<Vec<usize>> {
     let mut first_match_indices = vec![];
     let mut pattern_iter = pattern.iter().peekable();
     for (idx, &c) in choice.iter().enumerate() {
         match pattern_iter.peek() {
             Some(&&p) => {
-                if char_equal(c, p, case_sensitive) {
+                if char_equal(c.into(), p.into(), case_sensitive, case_folding) {
                     first_match_indices.push(idx);
                     let _ = pattern_iter.next();
                 }
@@ -26,40 +34,431 @@ pub fn cheap_matches(
     }
 }
 
-/// Given 2 character, check if they are equal (considering ascii case)
-/// e.g. ('a', 'A', true) => false
-/// e.g. ('a', 'A', false) => true
+/// Same as `cheap_matches` on ASCII bytes, but the next occurrence of every pattern byte is
+/// located by memchr, which is much faster to reject the choices that don't match.
+pub fn cheap_matches_ascii(
+    choice: &[u8],
+    pattern: &[u8],
+    case_sensitive: bool,
+) -> Option<Vec<usize>> {
+    let mut first_match_indices = Vec::with_capacity(pattern.len());
+    find_subsequence(choice, pattern, case_sensitive, |idx| {
+        first_match_indices.push(idx)
+    })
+    .then_some(first_match_indices)
+}
+
+/// Check if the choice may match the pattern by scanning its UTF-8 bytes, before it's decoded
+/// into characters. It could only tell when the pattern is ASCII and ASCII characters match
+/// only ASCII ones, i.e. the choice is ASCII too, or there's no normalization or unicode case
+/// folding. Otherwise returns true.
+pub fn may_match(
+    choice: &str,
+    pattern: &str,
+    case_sensitive: bool,
+    case_folding: CaseFolding,
+    normalize: bool,
+) -> bool {
+    let decidable = pattern.is_ascii()
+        && ((!normalize && (case_sensitive || case_folding == CaseFolding::Ascii))
+            || choice.is_ascii());
+    // ASCII bytes never appear inside the encoding of non-ASCII characters
+    !decidable
+        || find_subsequence(
+            choice.as_bytes(),
+            pattern.as_bytes(),
+            case_sensitive,
+            |_| {},
+        )
+}
+
+/// Find the pattern bytes in the choice one after another, report the (first) matched indices
+/// to `on_match` and return if the whole pattern is found.
 #[inline]
-pub fn char_equal(a: char, b: char, case_sensitive: bool) -> bool {
-    if case_sensitive {
+fn find_subsequence(
+    choice: &[u8],
+    pattern: &[u8],
+    case_sensitive: bool,
+    mut on_match: impl FnMut(usize),
+) -> bool {
+    let mut start = 0;
+    for &p in pattern {
+        let haystack = &choice[start..];
+        let found = if case_sensitive || !p.is_ascii_alphabetic() {
+            memchr(p, haystack)
+        } else {
+            memchr2(p.to_ascii_lowercase(), p.to_ascii_uppercase(), haystack)
+        };
+
+        match found {
+            Some(offset) => {
+                on_match(start + offset);
+                start += offset + 1;
+            }
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Stands for a mistyped character of the pattern, which matches any character, see
+/// `typo_variants`. It is a noncharacter, i.e. reserved for internal use and never typed.
+pub const WILDCARD: char = '\u{FDD0}';
+
+/// Given 2 character, check if they are equal (considering case folding)
+/// e.g. ('a', 'A', true, _) => false
+/// e.g. ('a', 'A', false, _) => true
+/// e.g. ('ä', 'Ä', false, Ascii) => false
+/// e.g. ('ä', 'Ä', false, Simple) => true
+/// `b` is the pattern character, and any `a` is equal to `WILDCARD`.
+#[inline]
+pub fn char_equal(a: char, b: char, case_sensitive: bool, case_folding: CaseFolding) -> bool {
+    if b == WILDCARD {
+        true
+    } else if case_sensitive {
         a == b
-    } else {
+    } else if case_folding == CaseFolding::Ascii || (a.is_ascii() && b.is_ascii()) {
         a.eq_ignore_ascii_case(&b)
+    } else {
+        fold_case(a) == fold_case(b)
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum CharType {
-    NonWord,
-    Lower,
-    Upper,
-    Number,
+/// Unicode simple case folding, i.e. the folding that maps one character to exactly one
+/// character. e.g. 'Ä' => 'ä', 'Σ' => 'σ', 'ς' => 'σ', 'ẞ' => 'ß'
+pub fn fold_case(ch: char) -> char {
+    if ch.is_ascii() {
+        return ch.to_ascii_lowercase();
+    }
+
+    // dotless i has no folding, but its upper case 'I' would fold to 'i'
+    if ch == 'ı' {
+        return ch;
+    }
+
+    // folding is (mostly) lower case of the upper case, it makes sure that characters with the
+    // same upper case are folded together, e.g. 'ς' and 'σ'
+    let upper = single_char(ch.to_uppercase()).unwrap_or(ch);
+    single_char(upper.to_lowercase())
+        .or_else(|| single_char(ch.to_lowercase()))
+        .unwrap_or(ch)
+}
+
+/// Unicode full case folding of a character, which might expand to multiple characters.
+/// e.g. 'ß' => "ss", 'ﬁ' => "fi", 'İ' => "i̇"
+pub fn full_fold_case(ch: char) -> Vec<char> {
+    if needs_expansion(ch) {
+        ch.to_uppercase().flat_map(char::to_lowercase).collect()
+    } else {
+        vec![fold_case(ch)]
+    }
+}
+
+fn single_char(mut iter: impl Iterator<Item = char>) -> Option<char> {
+    let ch = iter.next()?;
+    match iter.next() {
+        Some(_) => None,
+        None => Some(ch),
+    }
+}
+
+/// Expand the characters that fold to multiple characters under full case folding, e.g. "ß" to
+/// "ss", so that the characters could still be compared one by one with simple folding.
+/// Upper case characters are expanded to upper case to keep the case information, e.g. "ẞ" to
+/// "SS".
+///
+/// Return the index of the original character for each of the expanded characters, or None if
+/// nothing is expanded.
+pub fn expand_full_folding(chars: &mut Vec<char>) -> Option<Vec<usize>> {
+    if !chars.iter().any(|&ch| needs_expansion(ch)) {
+        return None;
+    }
+
+    let original = std::mem::take(chars);
+    let mut original_indices = Vec::with_capacity(original.len());
+    for (idx, ch) in original.into_iter().enumerate() {
+        if !needs_expansion(ch) {
+            chars.push(ch);
+            original_indices.push(idx);
+            continue;
+        }
+
+        let folded = full_fold_case(ch);
+        let upper: Vec<char> = ch.to_uppercase().collect();
+        let expanded = if ch.is_uppercase() && upper.len() == folded.len() {
+            upper
+        } else {
+            folded
+        };
+        for expanded_ch in expanded {
+            chars.push(expanded_ch);
+            original_indices.push(idx);
+        }
+    }
+
+    Some(original_indices)
 }
 
 #[inline]
-pub fn char_type_of(ch: char) -> CharType {
-    if ch.is_lowercase() {
-        CharType::Lower
-    } else if ch.is_uppercase() {
-        CharType::Upper
-    } else if ch.is_numeric() {
-        CharType::Number
+fn needs_expansion(ch: char) -> bool {
+    !ch.is_ascii()
+        && ch
+            .to_uppercase()
+            .flat_map(char::to_lowercase)
+            .nth(1)
+            .is_some()
+}
+
+/// Map a Latin letter with diacritics to its base letter, e.g. 'é' => 'e', 'Ł' => 'L'.
+/// Covers Latin-1 Supplement and Latin Extended-A, other characters are returned as is.
+pub fn normalize_char(ch: char) -> char {
+    if ch.is_ascii() {
+        return ch;
+    }
+
+    match ch {
+        'À'..='Å' | 'Ā' | 'Ă' | 'Ą' => 'A',
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => 'C',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'Ď' | 'Đ' => 'D',
+        'ď' | 'đ' => 'd',
+        'È'..='Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => 'E',
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => 'G',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'Ĥ' | 'Ħ' => 'H',
+        'ĥ' | 'ħ' => 'h',
+        'Ì'..='Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => 'I',
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'Ĵ' => 'J',
+        'ĵ' => 'j',
+        'Ķ' => 'K',
+        'ķ' => 'k',
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => 'L',
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => 'N',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'Ò'..='Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => 'O',
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'Ŕ' | 'Ŗ' | 'Ř' => 'R',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' => 'S',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'Ţ' | 'Ť' | 'Ŧ' => 'T',
+        'ţ' | 'ť' | 'ŧ' => 't',
+        'Ù'..='Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => 'U',
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'Ŵ' => 'W',
+        'ŵ' => 'w',
+        'Ý' | 'Ŷ' | 'Ÿ' => 'Y',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'Ź' | 'Ż' | 'Ž' => 'Z',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => ch,
+    }
+}
+
+/// Combining diacritical marks, e.g. the second character of the decomposed "e\u{301}"
+#[inline]
+fn is_combining_mark(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Strip the diacritics of the characters, i.e. map Latin letters to their base letter and
+/// remove the combining marks.
+///
+/// Return the index of the original character for each of the remaining characters, or None if
+/// no character is removed.
+pub fn normalize_chars(chars: &mut Vec<char>) -> Option<Vec<usize>> {
+    let has_marks = chars.iter().any(|&ch| is_combining_mark(ch));
+    if !has_marks {
+        for ch in chars.iter_mut() {
+            *ch = normalize_char(*ch);
+        }
+        return None;
+    }
+
+    let original = std::mem::take(chars);
+    let mut original_indices = Vec::with_capacity(original.len());
+    for (idx, ch) in original.into_iter().enumerate() {
+        if !is_combining_mark(ch) {
+            chars.push(normalize_char(ch));
+            original_indices.push(idx);
+        }
+    }
+
+    Some(original_indices)
+}
+
+/// Rewrite the characters of choice or pattern before comparing them, i.e. strip the
+/// diacritics if `normalize` and expand the characters for full case folding if
+/// `expand_folding`.
+///
+/// Return the index of the original character for each of the resulting characters, or None if
+/// the indices are unchanged.
+pub fn transform_chars(
+    chars: &mut Vec<char>,
+    normalize: bool,
+    expand_folding: bool,
+) -> Option<Vec<usize>> {
+    let normalized = if normalize {
+        normalize_chars(chars)
+    } else {
+        None
+    };
+    let expanded = if expand_folding {
+        expand_full_folding(chars)
+    } else {
+        None
+    };
+
+    match (normalized, expanded) {
+        (Some(normalized), Some(expanded)) => {
+            Some(expanded.into_iter().map(|idx| normalized[idx]).collect())
+        }
+        (normalized, None) => normalized,
+        (None, expanded) => expanded,
+    }
+}
+
+/// Map the indices of transformed characters back to the original ones, see `transform_chars`
+#[allow(clippy::unnecessary_cast)]
+pub fn map_original_indices(indices: &mut Vec<IndexType>, original_indices: &[usize]) {
+    for idx in indices.iter_mut() {
+        *idx = original_indices[*idx as usize] as IndexType;
+    }
+    indices.dedup();
+}
+
+/// Push the units to match of the text, i.e. its characters, or the first character of every
+/// extended grapheme cluster if `graphemes`, so that a cluster is matched by its base
+/// character, e.g. "e\u{301}" by "e" and "👍🏽" by "👍".
+pub fn extend_units(units: &mut Vec<char>, text: &str, graphemes: bool) {
+    if graphemes {
+        units.extend(
+            text.graphemes(true)
+                .filter_map(|grapheme| grapheme.chars().next()),
+        );
     } else {
-        CharType::NonWord
+        units.extend(text.chars());
+    }
+}
+
+/// The number of units to match of the text after it is transformed, see `extend_units` and
+/// `transform_chars`, e.g. to bound the score of a pattern.
+pub fn count_units(text: &str, graphemes: bool, normalize: bool, expand_folding: bool) -> usize {
+    let mut units = Vec::new();
+    extend_units(&mut units, text, graphemes);
+    transform_chars(&mut units, normalize, expand_folding);
+    units.len()
+}
+
+/// The most characters a pattern could match under any of the options, i.e. its characters
+/// expanded by full case folding, see `count_units`.
+pub fn max_matched_chars(pattern: &str) -> usize {
+    pattern.chars().map(|ch| full_fold_case(ch).len()).sum()
+}
+
+/// Check if every unit of the text is an ASCII character, see `extend_units`. "\r\n" is the
+/// only ASCII grapheme cluster of multiple characters.
+pub fn is_ascii_units(text: &str, graphemes: bool) -> bool {
+    text.is_ascii() && !(graphemes && text.contains("\r\n"))
+}
+
+/// Check if the string contains upper case characters, considering only ASCII ones unless
+/// unicode case folding is enabled.
+pub fn contains_upper(string: &str, case_folding: CaseFolding) -> bool {
+    match case_folding {
+        CaseFolding::Ascii => string.chars().any(|ch| ch.is_ascii_uppercase()),
+        _ => string.chars().any(char::is_uppercase),
+    }
+}
+
+/// Same as `contains_upper`, but on the characters, or the bytes, decoded already
+pub fn contains_upper_atoms<C: Copy + Into<char>>(atoms: &[C], case_folding: CaseFolding) -> bool {
+    let mut chars = atoms.iter().map(|&atom| -> char { atom.into() });
+    match case_folding {
+        CaseFolding::Ascii => chars.any(|ch| ch.is_ascii_uppercase()),
+        _ => chars.any(char::is_uppercase),
+    }
+}
+
+/// The number of pattern characters found in the choice one after another, i.e. the length of
+/// the longest prefix of the pattern that `cheap_matches` would find.
+pub fn matched_prefix_len<C: Copy + Into<char>>(
+    choice: &[C],
+    pattern: &[C],
+    case_sensitive: bool,
+    case_folding: CaseFolding,
+) -> usize {
+    let mut matched = 0;
+    for &c in choice {
+        match pattern.get(matched) {
+            Some(&p) => {
+                if char_equal(c.into(), p.into(), case_sensitive, case_folding) {
+                    matched += 1;
+                }
+            }
+            None => break,
+        }
+    }
+    matched
+}
+
+/// The patterns one typo away from `pattern`, i.e. with a character replaced by `WILDCARD` (a
+/// mistyped one), a character dropped (an extra one) or two adjacent characters swapped. The
+/// wildcards are left as they are. Variants that are obviously the same, e.g. dropping either
+/// "z" of "zz", are listed only once, and the empty pattern is never listed.
+///
+/// Only the typos changing the characters up to `end` are listed: when the pattern doesn't
+/// match and `end` is its `matched_prefix_len`, the other ones don't match either.
+pub fn typo_variants(pattern: &[char], end: usize) -> Vec<Vec<char>> {
+    let end = min(end + 1, pattern.len());
+    let mut variants = Vec::new();
+    for idx in 0..end {
+        if pattern[idx] == WILDCARD {
+            continue;
+        }
+        let mut variant = pattern.to_vec();
+        variant[idx] = WILDCARD;
+        variants.push(variant);
+    }
+
+    if pattern.len() > 1 {
+        for idx in 0..end {
+            if pattern[idx] == WILDCARD || (idx > 0 && pattern[idx] == pattern[idx - 1]) {
+                continue;
+            }
+            let mut variant = pattern.to_vec();
+            variant.remove(idx);
+            variants.push(variant);
+        }
     }
+
+    for idx in 1..min(end + 1, pattern.len()) {
+        if pattern[idx] == pattern[idx - 1]
+            || pattern[idx] == WILDCARD
+            || pattern[idx - 1] == WILDCARD
+        {
+            continue;
+        }
+        let mut variant = pattern.to_vec();
+        variant.swap(idx - 1, idx);
+        variants.push(variant);
+    }
+
+    variants
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum CharRole {
     Tail,
     Head,
@@ -80,12 +479,11 @@ pub enum CharRole {
 // Prev=Lower 0x00, 0x55, 0xaa, 0xff, // In word, Upper->Head;Lower->Tail
 // Prev=Upper 0x00, 0x55, 0x59, 0xff, // Ditto, but U(U)U->Tail
 // Prev=Separ 0x00, 0xaa, 0xaa, 0xff, // After separator, like at start
-pub fn char_role(prev: char, cur: char) -> CharRole {
-    use self::CharRole::*;
-    use self::CharType::*;
-    match (char_type_of(prev), char_type_of(cur)) {
-        (Lower, Upper) | (NonWord, Lower) | (NonWord, Upper) => Head,
-        _ => Tail,
+pub fn char_role(prev: CharType, cur: CharType) -> CharRole {
+    match (prev, cur) {
+        (CharType::Lower, CharType::Upper) => CharRole::Head,
+        (prev, CharType::Lower) | (prev, CharType::Upper) if !prev.is_word() => CharRole::Head,
+        _ => CharRole::Tail,
     }
 }
 
@@ -127,17 +525,14 @@ pub fn filter_and_sort(
 
 #[allow(dead_code)]
 pub fn wrap_matches(line: &str, indices: &[IndexType]) -> String {
-    let mut ret = String::new();
-    let mut peekable = indices.iter().peekable();
-    for (idx, ch) in line.chars().enumerate() {
-        let next_id = **peekable.peek().unwrap_or(&&(line.len() as IndexType));
-        if next_id == (idx as IndexType) {
-            ret.push_str(format!("[{}]", ch).as_str());
-            peekable.next();
-        } else {
-            ret.push(ch);
-        }
-    }
-
-    ret
+    // every matched character is wrapped on its own, so that the indices are easy to tell
+    segments(line, indices)
+        .map(|(text, matched)| {
+            if matched {
+                text.chars().map(|ch| format!("[{}]", ch)).collect()
+            } else {
+                text.to_string()
+            }
+        })
+        .collect()
 }